@@ -0,0 +1,329 @@
+//! A computer player that picks moves with expectimax search.
+//!
+//! The search alternates between the player's moves, where the best one is
+//! taken, and tile spawns, where the outcomes are averaged by how likely
+//! they are under the [`SpawnDistribution`]. Boards at the bottom of the
+//! search are scored by [`Heuristics`].
+
+use crate::engine::{BoardShift, Grid, SpawnDistribution};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Every direction, in the order they are tried.
+pub const SHIFTS: [BoardShift; 4] = [
+    BoardShift::Left,
+    BoardShift::Right,
+    BoardShift::Up,
+    BoardShift::Down,
+];
+
+/// The score of a board with no moves left, well below any heuristic score.
+pub const LOST: f32 = -1.0e6;
+
+/// Chains of spawns less likely than this aren't searched any deeper, which
+/// keeps boards with lots of empty cells from blowing up the search.
+const MIN_PROBABILITY: f32 = 1.0e-4;
+
+/// How much each feature of a board counts towards its score. Tiles are
+/// measured by their power of two, so a 1024 next to a 2048 is a step of 1.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Heuristics {
+    /// Bonus per empty cell.
+    pub empty: f32,
+    /// Penalty for rows and columns that don't steadily rise or fall.
+    pub monotonicity: f32,
+    /// Penalty for the differences between neighbouring tiles.
+    pub smoothness: f32,
+    /// Bonus for keeping the biggest tile in a corner.
+    pub corner: f32,
+}
+
+impl Default for Heuristics {
+    fn default() -> Self {
+        Heuristics {
+            empty: 2.7,
+            monotonicity: 1.0,
+            smoothness: 0.1,
+            corner: 1.0,
+        }
+    }
+}
+
+/// A tile's power of two, 0 for an empty cell.
+fn rank(value: Option<u32>) -> f32 {
+    value.map_or(0.0, |value| (value as f32).log2())
+}
+
+impl Heuristics {
+    pub fn score(&self, grid: &Grid) -> f32 {
+        let (width, height) = (grid.width(), grid.height());
+        let rows = (0..height).map(|y| (0..width).map(|x| (x, y)).collect::<Vec<_>>());
+        let columns = (0..width).map(|x| (0..height).map(|y| (x, y)).collect::<Vec<_>>());
+
+        let mut monotonicity = 0.0;
+        for line in rows.chain(columns) {
+            let (mut rising, mut falling) = (0.0, 0.0);
+            for pair in line.windows(2) {
+                let step = rank(grid.get(pair[1])) - rank(grid.get(pair[0]));
+                if step > 0.0 {
+                    rising += step;
+                } else {
+                    falling -= step;
+                }
+            }
+            monotonicity += f32::min(rising, falling);
+        }
+
+        let mut smoothness = 0.0;
+        for ((x, y), value) in grid.tiles() {
+            for neighbour in [(x + 1, y), (x, y + 1)] {
+                if neighbour.0 < width && neighbour.1 < height {
+                    if let Some(other) = grid.get(neighbour) {
+                        smoothness += (rank(Some(value)) - rank(Some(other))).abs();
+                    }
+                }
+            }
+        }
+
+        let max_tile = grid.max_tile();
+        let corners = [
+            (0, 0),
+            (width - 1, 0),
+            (0, height - 1),
+            (width - 1, height - 1),
+        ];
+        let corner = if corners.iter().any(|pos| grid.get(*pos) == max_tile) {
+            rank(max_tile)
+        } else {
+            0.0
+        };
+
+        self.empty * grid.empty_cells().count() as f32
+            - self.monotonicity * monotonicity
+            - self.smoothness * smoothness
+            + self.corner * corner
+    }
+}
+
+/// Searches `depth` moves ahead, including the one being chosen.
+pub struct Expectimax<'a> {
+    spawns: &'a SpawnDistribution,
+    heuristics: &'a Heuristics,
+    depth: u8,
+    /// Scores of boards already searched, by the moves left to search below.
+    cache: HashMap<(Grid, u8), f32>,
+    /// When to give up, for searches that have to fit in a time budget.
+    deadline: Option<Instant>,
+    timed_out: bool,
+}
+
+impl<'a> Expectimax<'a> {
+    pub fn new(spawns: &'a SpawnDistribution, heuristics: &'a Heuristics, depth: u8) -> Self {
+        Expectimax {
+            spawns,
+            heuristics,
+            depth: depth.max(1),
+            cache: HashMap::new(),
+            deadline: None,
+            timed_out: false,
+        }
+    }
+
+    /// Abandons the search once `deadline` passes. Check [`Self::timed_out`]
+    /// before trusting the scores.
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn timed_out(&self) -> bool {
+        self.timed_out
+    }
+
+    /// The expected score after each move that changes the board, best first.
+    pub fn scores(&mut self, grid: &Grid) -> Vec<(BoardShift, f32)> {
+        let mut scores: Vec<(BoardShift, f32)> = SHIFTS
+            .iter()
+            .filter_map(|shift| {
+                let mut next = grid.clone();
+                next.apply_move(*shift).changed().then(|| {
+                    let score = self.chance(&next, self.depth - 1, 1.0);
+                    (*shift, score)
+                })
+            })
+            .collect();
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scores
+    }
+
+    /// The move with the best expected score, `None` when the game is over.
+    pub fn best_move(&mut self, grid: &Grid) -> Option<BoardShift> {
+        self.scores(grid).first().map(|(shift, _)| *shift)
+    }
+
+    /// The player's turn: the best of the moves available.
+    fn max(&mut self, grid: &Grid, depth: u8, probability: f32) -> f32 {
+        let mut best = None;
+        for shift in SHIFTS {
+            let mut next = grid.clone();
+            if next.apply_move(shift).changed() {
+                let score = self.chance(&next, depth - 1, probability);
+                best = Some(best.map_or(score, |best: f32| best.max(score)));
+            }
+        }
+
+        best.unwrap_or(LOST)
+    }
+
+    /// A tile spawns: the average over every cell and value it could take.
+    fn chance(&mut self, grid: &Grid, depth: u8, probability: f32) -> f32 {
+        if self.timed_out
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            self.timed_out = true;
+            return 0.0;
+        }
+        if depth == 0 || probability < MIN_PROBABILITY {
+            return self.heuristics.score(grid);
+        }
+        if let Some(score) = self.cache.get(&(grid.clone(), depth)) {
+            return *score;
+        }
+
+        let empty: Vec<(u8, u8)> = grid.empty_cells().collect();
+        if empty.is_empty() {
+            return self.heuristics.score(grid);
+        }
+        let total: u32 = self.spawns.weights().iter().map(|(_, weight)| weight).sum();
+        let mut expected = 0.0;
+
+        for pos in empty.iter() {
+            for (value, weight) in self.spawns.weights() {
+                if *weight == 0 {
+                    continue;
+                }
+
+                let chance = *weight as f32 / total as f32 / empty.len() as f32;
+                let mut next = grid.clone();
+                next.set(*pos, Some(*value));
+                expected += chance * self.max(&next, depth, probability * chance);
+            }
+        }
+
+        if !self.timed_out {
+            self.cache.insert((grid.clone(), depth), expected);
+        }
+        expected
+    }
+}
+
+/// Searches one move ahead, then two and so on up to `max_depth`, for as long
+/// as `budget` allows. Returns the scores from the deepest search that
+/// finished. The first is always finished, however long it takes.
+pub fn timed_scores(
+    grid: &Grid,
+    spawns: &SpawnDistribution,
+    heuristics: &Heuristics,
+    budget: Duration,
+    max_depth: u8,
+) -> Vec<(BoardShift, f32)> {
+    let deadline = Instant::now() + budget;
+    let mut best = Expectimax::new(spawns, heuristics, 1).scores(grid);
+
+    for depth in 2..=max_depth {
+        let mut search = Expectimax::new(spawns, heuristics, depth).with_deadline(deadline);
+        let scores = search.scores(grid);
+        if search.timed_out() {
+            break;
+        }
+        best = scores;
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A grid from rows of values, top row first, 0 for an empty cell.
+    fn grid(rows: &[&[u32]]) -> Grid {
+        let mut grid = Grid::new(rows[0].len() as u8, rows.len() as u8);
+        for (row, values) in rows.iter().rev().enumerate() {
+            for (x, value) in values.iter().enumerate() {
+                grid.set((x as u8, row as u8), (*value != 0).then_some(*value));
+            }
+        }
+        grid
+    }
+
+    fn shifts(scores: &[(BoardShift, f32)]) -> Vec<BoardShift> {
+        let mut shifts: Vec<_> = scores.iter().map(|(shift, _)| *shift).collect();
+        shifts.sort_by_key(|shift| SHIFTS.iter().position(|other| other == shift));
+        shifts
+    }
+
+    #[test]
+    fn keeps_the_biggest_tile_in_a_corner() {
+        let heuristics = Heuristics::default();
+        let corner = grid(&[&[8, 0, 0, 0], &[0, 0, 0, 0], &[0, 0, 0, 0], &[0, 0, 0, 0]]);
+        let middle = grid(&[&[0, 0, 0, 0], &[0, 8, 0, 0], &[0, 0, 0, 0], &[0, 0, 0, 0]]);
+
+        assert!(heuristics.score(&corner) > heuristics.score(&middle));
+    }
+
+    #[test]
+    fn best_move_takes_the_merges() {
+        let spawns = SpawnDistribution::default();
+        let heuristics = Heuristics::default();
+        let board = grid(&[
+            &[0, 0, 0, 0],
+            &[0, 0, 0, 0],
+            &[0, 0, 0, 0],
+            &[128, 128, 64, 64],
+        ]);
+
+        // Looking further ahead, saving the merges for later can score higher
+        let best = Expectimax::new(&spawns, &heuristics, 1).best_move(&board);
+        assert!(matches!(best, Some(BoardShift::Left | BoardShift::Right)));
+    }
+
+    #[test]
+    fn scores_skip_moves_that_change_nothing() {
+        let spawns = SpawnDistribution::default();
+        let heuristics = Heuristics::default();
+        let board = grid(&[&[0, 0, 0], &[0, 0, 0], &[2, 0, 0]]);
+
+        let scores = Expectimax::new(&spawns, &heuristics, 2).scores(&board);
+        assert_eq!(shifts(&scores), [BoardShift::Right, BoardShift::Up]);
+    }
+
+    #[test]
+    fn a_board_without_moves_is_lost() {
+        let spawns = SpawnDistribution::default();
+        let heuristics = Heuristics::default();
+        let board = grid(&[&[2, 4], &[4, 2]]);
+        let mut search = Expectimax::new(&spawns, &heuristics, 3);
+
+        assert_eq!(search.max(&board, 2, 1.0), LOST);
+        assert_eq!(search.scores(&board), []);
+        assert_eq!(search.best_move(&board), None);
+    }
+
+    #[test]
+    fn timed_scores_always_finish_one_move_ahead() {
+        let spawns = SpawnDistribution::default();
+        let heuristics = Heuristics::default();
+        let board = grid(&[&[2, 0, 4, 0], &[0, 2, 0, 0], &[0, 0, 8, 0], &[4, 0, 0, 2]]);
+
+        let timed = timed_scores(&board, &spawns, &heuristics, Duration::ZERO, 6);
+        let shallow = Expectimax::new(&spawns, &heuristics, 1).scores(&board);
+        assert_eq!(timed, shallow);
+    }
+}
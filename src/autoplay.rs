@@ -0,0 +1,122 @@
+//! The computer player, switched on and off with the Autoplay key (I).
+//!
+//! It picks moves with [`ai::timed_scores`], searching as deep as
+//! `ai.move_ms` allows up to `ai.depth`, and plays them through the move
+//! queue like any other input, at most `ai.moves_per_second` of them.
+
+use crate::{
+    controls::{Action, ActionEvent},
+    dispatch_moves, queue_moves, FontSpec, Game, MoveQueue, RunState, Settings, TileSpawns,
+};
+use bevy::prelude::*;
+use std::time::Duration;
+use two_oh_four_eight::ai;
+
+pub struct AutoplayPlugin;
+
+impl Plugin for AutoplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Autoplay>()
+            .add_systems(Startup, spawn_autoplay_hud)
+            .add_systems(
+                Update,
+                (
+                    toggle_autoplay,
+                    autoplay
+                        .after(queue_moves)
+                        .before(dispatch_moves)
+                        .run_if(in_state(RunState::Playing)),
+                    autoplay_hud,
+                )
+                    .chain(),
+            );
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Autoplay {
+    enabled: bool,
+    /// Time left before the next move can be made.
+    cooldown: Timer,
+}
+
+fn toggle_autoplay(mut action_reader: EventReader<ActionEvent>, mut autoplay: ResMut<Autoplay>) {
+    for ActionEvent(action) in action_reader.read() {
+        if *action == Action::Autoplay {
+            autoplay.enabled = !autoplay.enabled;
+            autoplay.cooldown.reset();
+        }
+    }
+}
+
+/// Queues the search's pick once the last move has been played and the
+/// cooldown is up.
+fn autoplay(
+    mut autoplay: ResMut<Autoplay>,
+    time: Res<Time>,
+    game: Res<Game>,
+    spawns: Res<TileSpawns>,
+    settings: Res<Settings>,
+    mut queue: ResMut<MoveQueue>,
+) {
+    if !autoplay.enabled {
+        return;
+    }
+
+    autoplay.cooldown.tick(time.delta());
+    if !autoplay.cooldown.finished() || !queue.is_empty() {
+        return;
+    }
+
+    let ai = &settings.ai;
+    let scores = ai::timed_scores(
+        &game.grid,
+        &spawns,
+        &ai.heuristics,
+        Duration::from_millis(ai.move_ms),
+        ai.depth,
+    );
+    if let Some((shift, _)) = scores.first() {
+        queue.push_back(*shift);
+    }
+
+    let interval = Duration::from_secs_f32(1.0 / ai.moves_per_second.max(0.1));
+    autoplay.cooldown.set_duration(interval);
+    autoplay.cooldown.reset();
+}
+
+#[derive(Component)]
+pub struct AutoplayHud;
+
+fn spawn_autoplay_hud(mut commands: Commands, font_spec: Res<FontSpec>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: font_spec.family.clone(),
+                font_size: 15.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(20.0),
+            right: Val::Px(50.0),
+            ..default()
+        }),
+        AutoplayHud,
+    ));
+}
+
+fn autoplay_hud(autoplay: Res<Autoplay>, mut query_hud: Query<&mut Text, With<AutoplayHud>>) {
+    if !autoplay.is_changed() {
+        return;
+    }
+
+    for mut text in query_hud.iter_mut() {
+        let label = if autoplay.enabled { "Autoplay on" } else { "" };
+        if text.sections[0].value != label {
+            text.sections[0].value = label.to_string();
+        }
+    }
+}
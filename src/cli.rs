@@ -0,0 +1,176 @@
+//! Command-line flags.
+
+use crate::{
+    simulate::{Format, Simulate},
+    BOARD_SIZE_RANGE,
+};
+use bevy::prelude::*;
+use std::{path::PathBuf, str::FromStr};
+use two_oh_four_eight::simulation::{Simulation, Strategy};
+
+const USAGE: &str = "usage: two-oh-four-eight [--seed <number>] [--replay <file>] [--tui]
+       two-oh-four-eight simulate [--strategy random|greedy|corner|expectimax]
+           [--games <n>] [--seed <first seed>] [--threads <n>] [--size <w>x<h>]
+           [--depth <n>] [--win-target <tile>] [--format table|json|csv] [--output <file>]";
+
+#[derive(Resource, Debug, Default, Clone)]
+pub struct Cli {
+    /// Seeds every new game, taking precedence over the seed in settings.
+    pub seed: Option<u64>,
+    /// A replay file to watch instead of playing.
+    pub replay: Option<PathBuf>,
+    /// Play in the terminal instead of opening a window.
+    pub tui: bool,
+    /// Play games headless with `simulate` and report on them, instead of
+    /// playing.
+    pub simulate: Option<Simulate>,
+}
+
+impl Cli {
+    pub fn parse() -> Result<Self, String> {
+        Self::parse_from(std::env::args().skip(1))
+    }
+
+    fn parse_from(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut cli = Cli::default();
+        let mut args = args.into_iter().peekable();
+
+        if args.peek().is_some_and(|arg| arg == "simulate") {
+            args.next();
+            cli.simulate = Some(parse_simulate(args)?);
+            return Ok(cli);
+        }
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--seed" => {
+                    let value = args
+                        .next()
+                        .ok_or(format!("--seed needs a value\n{USAGE}"))?;
+                    let seed = value
+                        .parse()
+                        .map_err(|_| format!("invalid seed {value:?}\n{USAGE}"))?;
+                    cli.seed = Some(seed);
+                }
+                "--replay" => {
+                    let path = args
+                        .next()
+                        .ok_or(format!("--replay needs a file\n{USAGE}"))?;
+                    cli.replay = Some(path.into());
+                }
+                "--tui" => cli.tui = true,
+                "--help" | "-h" => return Err(USAGE.to_string()),
+                _ => return Err(format!("unknown argument {arg:?}\n{USAGE}")),
+            }
+        }
+
+        if cli.tui && cli.replay.is_some() {
+            return Err(format!("--replay can't be watched with --tui\n{USAGE}"));
+        }
+
+        Ok(cli)
+    }
+}
+
+fn parse_simulate(args: impl IntoIterator<Item = String>) -> Result<Simulate, String> {
+    let mut simulation = Simulation::default();
+    let mut format = Format::default();
+    let mut output = None;
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{arg} needs a value\n{USAGE}"));
+        match arg.as_str() {
+            "--strategy" => simulation.strategy = parse_value::<Strategy>(&arg, value()?)?,
+            "--games" => simulation.games = parse_value(&arg, value()?)?,
+            "--seed" => simulation.seed = parse_value(&arg, value()?)?,
+            "--threads" => simulation.threads = parse_value(&arg, value()?)?,
+            "--depth" => simulation.depth = parse_value(&arg, value()?)?,
+            "--win-target" => simulation.win_target = parse_value(&arg, value()?)?,
+            "--size" => {
+                let size = value()?;
+                let parsed = size
+                    .split_once('x')
+                    .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)));
+                let Some((width, height)) = parsed.filter(|(width, height)| {
+                    BOARD_SIZE_RANGE.contains(width) && BOARD_SIZE_RANGE.contains(height)
+                }) else {
+                    return Err(format!("invalid board size {size:?}\n{USAGE}"));
+                };
+                (simulation.width, simulation.height) = (width, height);
+            }
+            "--format" => format = parse_value(&arg, value()?)?,
+            "--output" => output = Some(value()?.into()),
+            "--help" | "-h" => return Err(USAGE.to_string()),
+            _ => return Err(format!("unknown argument {arg:?}\n{USAGE}")),
+        }
+    }
+
+    Ok(Simulate {
+        simulation,
+        format,
+        output,
+    })
+}
+
+fn parse_value<T: FromStr>(arg: &str, value: String) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value {value:?} for {arg}\n{USAGE}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Result<Cli, String> {
+        Cli::parse_from(line.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn parses_game_flags() {
+        let cli = parse("--seed 42 --tui").unwrap();
+        assert_eq!(cli.seed, Some(42));
+        assert!(cli.tui);
+        assert!(cli.replay.is_none() && cli.simulate.is_none());
+
+        let cli = parse("--replay game.ron").unwrap();
+        assert_eq!(cli.replay, Some(PathBuf::from("game.ron")));
+    }
+
+    #[test]
+    fn rejects_bad_game_flags() {
+        assert!(parse("--seed").is_err());
+        assert!(parse("--seed lots").is_err());
+        assert!(parse("--fast").is_err());
+        assert!(parse("--tui --replay game.ron").is_err());
+    }
+
+    #[test]
+    fn parses_simulate() {
+        let cli = parse(
+            "simulate --strategy greedy --games 10 --seed 5 --size 5x6 --format csv --output out.csv",
+        )
+        .unwrap();
+        let simulate = cli.simulate.unwrap();
+
+        assert_eq!(simulate.simulation.strategy, Strategy::Greedy);
+        assert_eq!(simulate.simulation.games, 10);
+        assert_eq!(simulate.simulation.seed, 5);
+        assert_eq!(
+            (simulate.simulation.width, simulate.simulation.height),
+            (5, 6)
+        );
+        assert_eq!(simulate.format, Format::Csv);
+        assert_eq!(simulate.output, Some(PathBuf::from("out.csv")));
+    }
+
+    #[test]
+    fn rejects_bad_simulate_flags() {
+        assert!(parse("simulate --strategy clever").is_err());
+        assert!(parse("simulate --size 9x9").is_err());
+        assert!(parse("simulate --size 4").is_err());
+        assert!(parse("simulate --games").is_err());
+        assert!(parse("simulate --tui").is_err());
+    }
+}
@@ -14,12 +14,104 @@ pub const TILE_PLACEHOLDER: Color = Color::Lcha {
     alpha: 1.0,
 };
 
-pub const TILE: Color = Color::Lcha {
-    lightness: 0.85,
-    chroma: 0.5,
-    hue: 315.0,
-    alpha: 1.0,
-};
+/// Tile backgrounds for 2, 4, 8 and so on up to 2048, getting darker and
+/// warmer as the value grows.
+const TILE_RAMP: [Color; 11] = [
+    Color::Lcha {
+        lightness: 0.90,
+        chroma: 0.15,
+        hue: 315.0,
+        alpha: 1.0,
+    },
+    Color::Lcha {
+        lightness: 0.85,
+        chroma: 0.3,
+        hue: 315.0,
+        alpha: 1.0,
+    },
+    Color::Lcha {
+        lightness: 0.78,
+        chroma: 0.5,
+        hue: 330.0,
+        alpha: 1.0,
+    },
+    Color::Lcha {
+        lightness: 0.72,
+        chroma: 0.6,
+        hue: 345.0,
+        alpha: 1.0,
+    },
+    Color::Lcha {
+        lightness: 0.66,
+        chroma: 0.7,
+        hue: 0.0,
+        alpha: 1.0,
+    },
+    Color::Lcha {
+        lightness: 0.60,
+        chroma: 0.8,
+        hue: 15.0,
+        alpha: 1.0,
+    },
+    Color::Lcha {
+        lightness: 0.80,
+        chroma: 0.6,
+        hue: 60.0,
+        alpha: 1.0,
+    },
+    Color::Lcha {
+        lightness: 0.78,
+        chroma: 0.7,
+        hue: 70.0,
+        alpha: 1.0,
+    },
+    Color::Lcha {
+        lightness: 0.76,
+        chroma: 0.8,
+        hue: 80.0,
+        alpha: 1.0,
+    },
+    Color::Lcha {
+        lightness: 0.74,
+        chroma: 0.9,
+        hue: 90.0,
+        alpha: 1.0,
+    },
+    Color::Lcha {
+        lightness: 0.72,
+        chroma: 1.0,
+        hue: 100.0,
+        alpha: 1.0,
+    },
+];
+
+/// The background for a tile worth `value`. Past 2048 the hue keeps turning
+/// on a darker base so every value still looks different.
+pub fn tile(value: u32) -> Color {
+    let step = value.max(2).ilog2() as usize - 1;
+
+    match TILE_RAMP.get(step) {
+        Some(color) => *color,
+        None => Color::Lcha {
+            lightness: 0.35,
+            chroma: 0.8,
+            hue: (220.0 + 40.0 * (step - TILE_RAMP.len()) as f32) % 360.0,
+            alpha: 1.0,
+        },
+    }
+}
+
+/// Black or white, whichever reads better on `background`.
+pub fn text_on(background: Color) -> Color {
+    let [r, g, b, _] = background.as_linear_rgba_f32();
+    let luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+
+    if luminance > 0.18 {
+        Color::BLACK
+    } else {
+        Color::WHITE
+    }
+}
 
 pub const SCORE_BOX: Color = Color::Lcha {
     lightness: 0.55,
@@ -28,6 +120,29 @@ pub const SCORE_BOX: Color = Color::Lcha {
     alpha: 1.0,
 };
 
+/// Flashed on the best score's box when it's beaten.
+pub const NEW_BEST: Color = Color::Lcha {
+    lightness: 0.80,
+    chroma: 0.6,
+    hue: 60.0,
+    alpha: 1.0,
+};
+
+pub const OVERLAY: Color = Color::Lcha {
+    lightness: 0.06,
+    chroma: 0.088,
+    hue: 281.0,
+    alpha: 0.85,
+};
+
+/// Lighter than [`OVERLAY`], so the board that ran out of moves still shows.
+pub const GAME_OVER_OVERLAY: Color = Color::Lcha {
+    lightness: 0.06,
+    chroma: 0.088,
+    hue: 281.0,
+    alpha: 0.6,
+};
+
 pub mod button {
     use bevy::prelude::Color;
 
@@ -52,3 +167,12 @@ pub mod button {
         alpha: 1.0,
     };
 }
+
+/// The arrow pointing out a hint's move, see-through so the tiles under it
+/// still show.
+pub const HINT: Color = Color::Lcha {
+    lightness: 0.95,
+    chroma: 0.3,
+    hue: 60.0,
+    alpha: 0.75,
+};
@@ -0,0 +1,646 @@
+//! Maps keys to the things a player can do.
+//!
+//! Systems act on [`ActionEvent`]s rather than reading the keyboard
+//! themselves, so every key can be rebound. Custom bindings are kept in the
+//! profile.
+//!
+//! Gamepads move with the D-pad or left stick, undo with X (West), redo with
+//! B (East), restart with Y (North) and pause with Start. While a menu has
+//! focus the same inputs move between its buttons instead, A presses one.
+//!
+//! Dragging across the board with the mouse, or swiping it on a touchscreen,
+//! moves as well.
+
+use crate::{
+    ui::{ConfirmRequest, Confirmable, Confirming, MenuEvent, MenuFocus, SettingsOpen},
+    Board, RunState, Settings,
+};
+use bevy::{input::InputSystem, prelude::*, window::PrimaryWindow};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+use two_oh_four_eight::engine::BoardShift;
+
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<KeyBindings>()
+            .add_event::<ActionEvent>()
+            .add_systems(
+                PreUpdate,
+                (
+                    (keyboard_actions, swipe_actions).run_if(
+                        not(resource_exists::<Rebinding>)
+                            .and_then(not(resource_exists::<Confirming>)),
+                    ),
+                    capture_binding.run_if(resource_exists::<Rebinding>),
+                    gamepad_actions,
+                )
+                    .after(InputSystem),
+            )
+            .add_systems(Update, game_actions);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    Undo,
+    Redo,
+    Restart,
+    Pause,
+    /// Hands the game to the computer player, or takes it back.
+    Autoplay,
+    /// Shows the move the computer player would make.
+    Hint,
+}
+
+impl Action {
+    pub const ALL: [Action; 10] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::Undo,
+        Action::Redo,
+        Action::Restart,
+        Action::Pause,
+        Action::Autoplay,
+        Action::Hint,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::MoveLeft => "Left",
+            Action::MoveRight => "Right",
+            Action::MoveUp => "Up",
+            Action::MoveDown => "Down",
+            Action::Undo => "Undo",
+            Action::Redo => "Redo",
+            Action::Restart => "Restart",
+            Action::Pause => "Pause",
+            Action::Autoplay => "Autoplay",
+            Action::Hint => "Hint",
+        }
+    }
+
+    pub fn from_shift(shift: BoardShift) -> Self {
+        match shift {
+            BoardShift::Left => Action::MoveLeft,
+            BoardShift::Right => Action::MoveRight,
+            BoardShift::Up => Action::MoveUp,
+            BoardShift::Down => Action::MoveDown,
+        }
+    }
+
+    /// The shift a move action makes, `None` for everything else.
+    pub fn shift(&self) -> Option<BoardShift> {
+        match self {
+            Action::MoveLeft => Some(BoardShift::Left),
+            Action::MoveRight => Some(BoardShift::Right),
+            Action::MoveUp => Some(BoardShift::Up),
+            Action::MoveDown => Some(BoardShift::Down),
+            _ => None,
+        }
+    }
+}
+
+/// A key, along with the modifiers that have to be held for it to count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Binding {
+    pub key: KeyCode,
+    #[serde(default)]
+    pub ctrl: bool,
+    #[serde(default)]
+    pub shift: bool,
+}
+
+impl Binding {
+    const fn key(key: KeyCode) -> Self {
+        Binding {
+            key,
+            ctrl: false,
+            shift: false,
+        }
+    }
+
+    const fn ctrl(key: KeyCode) -> Self {
+        Binding {
+            key,
+            ctrl: true,
+            shift: false,
+        }
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+
+        // `KeyZ` reads better as `Z`, `ArrowLeft` as `Left`
+        let name = format!("{:?}", self.key);
+        let name = ["Key", "Digit", "Arrow"]
+            .iter()
+            .find_map(|prefix| name.strip_prefix(prefix))
+            .unwrap_or(&name);
+        write!(f, "{name}")
+    }
+}
+
+/// The ready-made sets of bindings, which differ in the keys used to move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    Arrows,
+    Wasd,
+    Vim,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 3] = [Preset::Arrows, Preset::Wasd, Preset::Vim];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Preset::Arrows => "Arrows",
+            Preset::Wasd => "WASD",
+            Preset::Vim => "HJKL",
+        }
+    }
+}
+
+/// Which keys trigger each action. A key can only be bound to one action.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyBindings(BTreeMap<Action, Vec<Binding>>);
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings::preset(Preset::Arrows)
+    }
+}
+
+impl KeyBindings {
+    /// The arrow keys always move; the presets add their own keys on top.
+    pub fn preset(preset: Preset) -> Self {
+        let moves = match preset {
+            Preset::Arrows => [None; 4],
+            Preset::Wasd => [KeyCode::KeyA, KeyCode::KeyD, KeyCode::KeyW, KeyCode::KeyS].map(Some),
+            Preset::Vim => [KeyCode::KeyH, KeyCode::KeyL, KeyCode::KeyK, KeyCode::KeyJ].map(Some),
+        };
+        let arrows = [
+            KeyCode::ArrowLeft,
+            KeyCode::ArrowRight,
+            KeyCode::ArrowUp,
+            KeyCode::ArrowDown,
+        ];
+
+        let mut bindings = BTreeMap::new();
+        for ((action, arrow), extra) in Action::ALL.iter().zip(arrows).zip(moves) {
+            let keys = [Some(arrow), extra].into_iter().flatten();
+            bindings.insert(*action, keys.map(Binding::key).collect());
+        }
+
+        let redo = match preset {
+            Preset::Vim => vec![Binding::ctrl(KeyCode::KeyR)],
+            _ => vec![
+                Binding::ctrl(KeyCode::KeyY),
+                Binding {
+                    key: KeyCode::KeyZ,
+                    ctrl: true,
+                    shift: true,
+                },
+            ],
+        };
+        bindings.insert(
+            Action::Undo,
+            vec![Binding::ctrl(KeyCode::KeyZ), Binding::key(KeyCode::KeyU)],
+        );
+        bindings.insert(Action::Redo, redo);
+        bindings.insert(Action::Restart, vec![Binding::key(KeyCode::KeyR)]);
+        bindings.insert(
+            Action::Pause,
+            vec![Binding::key(KeyCode::Escape), Binding::key(KeyCode::KeyP)],
+        );
+        bindings.insert(Action::Autoplay, vec![Binding::key(KeyCode::KeyI)]);
+        bindings.insert(Action::Hint, vec![Binding::key(KeyCode::Slash)]);
+
+        KeyBindings(bindings)
+    }
+
+    /// Gives actions added since the bindings were saved their default
+    /// keys, leaving out any that have since been taken.
+    pub fn fill_missing(&mut self) {
+        let defaults = KeyBindings::default();
+        for action in Action::ALL {
+            if self.0.contains_key(&action) {
+                continue;
+            }
+
+            let free = defaults
+                .bindings(action)
+                .iter()
+                .filter(|binding| self.action_for(**binding).is_none())
+                .copied()
+                .collect();
+            self.0.insert(action, free);
+        }
+    }
+
+    pub fn bindings(&self, action: Action) -> &[Binding] {
+        self.0.get(&action).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn action_for(&self, binding: Binding) -> Option<Action> {
+        self.0
+            .iter()
+            .find(|(_, bindings)| bindings.contains(&binding))
+            .map(|(action, _)| *action)
+    }
+
+    /// Makes `binding` the only key for `action`. Fails with the action
+    /// already using it, if that's a different one.
+    pub fn bind(&mut self, action: Action, binding: Binding) -> Result<(), Action> {
+        match self.action_for(binding) {
+            Some(other) if other != action => Err(other),
+            _ => {
+                self.0.insert(action, vec![binding]);
+                Ok(())
+            }
+        }
+    }
+
+    /// Every binding that more than one action claims, with the first two
+    /// actions claiming it.
+    pub fn conflicts(&self) -> Vec<(Binding, Action, Action)> {
+        let mut seen: HashMap<Binding, Action> = HashMap::new();
+        let mut conflicts = Vec::new();
+
+        for (action, bindings) in self.0.iter() {
+            for binding in bindings {
+                match seen.get(binding) {
+                    Some(first) if first != action => {
+                        conflicts.push((*binding, *first, *action));
+                    }
+                    Some(_) => {}
+                    None => {
+                        seen.insert(*binding, *action);
+                    }
+                }
+            }
+        }
+
+        conflicts
+    }
+}
+
+/// Something the player asked to do.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActionEvent(pub Action);
+
+/// The controls screen is open. While it is, keys are captured for
+/// rebinding rather than played.
+#[derive(Resource, Default)]
+pub struct Rebinding {
+    /// The action waiting for a key, if any.
+    pub capturing: Option<Action>,
+    /// Why the last key pressed wasn't accepted.
+    pub message: String,
+}
+
+fn held(input: &ButtonInput<KeyCode>) -> (bool, bool) {
+    (
+        input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]),
+        input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]),
+    )
+}
+
+fn is_modifier(key: KeyCode) -> bool {
+    matches!(
+        key,
+        KeyCode::ControlLeft
+            | KeyCode::ControlRight
+            | KeyCode::ShiftLeft
+            | KeyCode::ShiftRight
+            | KeyCode::AltLeft
+            | KeyCode::AltRight
+            | KeyCode::SuperLeft
+            | KeyCode::SuperRight
+    )
+}
+
+fn keyboard_actions(
+    input: Res<ButtonInput<KeyCode>>,
+    keys: Res<KeyBindings>,
+    mut action_writer: EventWriter<ActionEvent>,
+) {
+    let (ctrl, shift) = held(&input);
+
+    for key in input.get_just_pressed() {
+        let binding = Binding {
+            key: *key,
+            ctrl,
+            shift,
+        };
+        if let Some(action) = keys.action_for(binding) {
+            action_writer.send(ActionEvent(action));
+        }
+    }
+}
+
+/// Turns D-pad presses and left stick flicks into moves, or into menu
+/// navigation while a menu has focus.
+///
+/// The stick only counts once it passes `stick_deadzone`, and has to come
+/// back inside `stick_rearm` before it can count again, so holding it over
+/// makes a single move.
+#[allow(clippy::too_many_arguments)]
+fn gamepad_actions(
+    gamepads: Res<Gamepads>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    settings: Res<Settings>,
+    menu: Res<MenuFocus>,
+    mut armed: Local<HashMap<Gamepad, bool>>,
+    mut action_writer: EventWriter<ActionEvent>,
+    mut menu_writer: EventWriter<MenuEvent>,
+) {
+    for gamepad in gamepads.iter() {
+        let pressed = |button_type| buttons.just_pressed(GamepadButton::new(gamepad, button_type));
+
+        let mut shift = [
+            (GamepadButtonType::DPadLeft, BoardShift::Left),
+            (GamepadButtonType::DPadRight, BoardShift::Right),
+            (GamepadButtonType::DPadUp, BoardShift::Up),
+            (GamepadButtonType::DPadDown, BoardShift::Down),
+        ]
+        .into_iter()
+        .find_map(|(button_type, shift)| pressed(button_type).then_some(shift));
+
+        let stick = Vec2::new(
+            axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX))
+                .unwrap_or_default(),
+            axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY))
+                .unwrap_or_default(),
+        );
+        let armed = armed.entry(gamepad).or_insert(true);
+        if *armed && stick.length() >= settings.stick_deadzone {
+            *armed = false;
+            shift = shift.or(Some(nearest_shift(stick)));
+        } else if !*armed && stick.length() <= settings.stick_rearm {
+            *armed = true;
+        }
+
+        if pressed(GamepadButtonType::Select) {
+            menu_writer.send(MenuEvent::Toggle);
+        }
+        if pressed(GamepadButtonType::Start) {
+            action_writer.send(ActionEvent(Action::Pause));
+        }
+
+        if menu.engaged() {
+            if let Some(shift) = shift {
+                menu_writer.send(MenuEvent::Move(shift));
+            }
+            if pressed(GamepadButtonType::South) {
+                menu_writer.send(MenuEvent::Press);
+            }
+            if pressed(GamepadButtonType::East) {
+                menu_writer.send(MenuEvent::Back);
+            }
+            continue;
+        }
+
+        let actions = [
+            (GamepadButtonType::West, Action::Undo),
+            (GamepadButtonType::East, Action::Redo),
+            (GamepadButtonType::North, Action::Restart),
+            (GamepadButtonType::LeftTrigger, Action::Hint),
+        ];
+        for (button_type, action) in actions {
+            if pressed(button_type) {
+                action_writer.send(ActionEvent(action));
+            }
+        }
+        if let Some(shift) = shift {
+            action_writer.send(ActionEvent(Action::from_shift(shift)));
+        }
+    }
+}
+
+/// The direction closest to `direction`, which points up for positive `y`.
+fn nearest_shift(direction: Vec2) -> BoardShift {
+    match direction {
+        Vec2 { x, y } if x.abs() > y.abs() && x < 0.0 => BoardShift::Left,
+        Vec2 { x, y } if x.abs() > y.abs() => BoardShift::Right,
+        Vec2 { y, .. } if y > 0.0 => BoardShift::Up,
+        _ => BoardShift::Down,
+    }
+}
+
+/// Turns a mouse drag or touch swipe that starts on the board into a move.
+///
+/// Swipes shorter than `swipe_min_distance` pixels are ignored, as are ones
+/// more than `swipe_angle_tolerance` degrees away from straight along a row
+/// or column, since it isn't clear which way those were meant to go.
+#[allow(clippy::too_many_arguments)]
+fn swipe_actions(
+    mouse: Res<ButtonInput<MouseButton>>,
+    touches: Res<Touches>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    boards: Query<(&Board, &GlobalTransform)>,
+    settings: Res<Settings>,
+    mut drag_start: Local<Option<Vec2>>,
+    mut action_writer: EventWriter<ActionEvent>,
+) {
+    let Ok((camera, camera_transform)) = cameras.get_single() else {
+        return;
+    };
+    let to_world = |position: Vec2| camera.viewport_to_world_2d(camera_transform, position);
+    let on_board = |point: &Vec2| {
+        boards.iter().any(|(board, transform)| {
+            let offset = *point - transform.translation().truncate();
+            offset.abs().cmple(board.size_px / 2.0).all()
+        })
+    };
+
+    let mut swipes = Vec::new();
+
+    let cursor = windows
+        .get_single()
+        .ok()
+        .and_then(Window::cursor_position)
+        .and_then(to_world);
+    if mouse.just_pressed(MouseButton::Left) {
+        *drag_start = cursor.filter(on_board);
+    }
+    if mouse.just_released(MouseButton::Left) {
+        if let (Some(start), Some(end)) = (drag_start.take(), cursor) {
+            swipes.push(end - start);
+        }
+    }
+
+    for touch in touches.iter_just_released() {
+        let start = to_world(touch.start_position()).filter(on_board);
+        if let (Some(start), Some(end)) = (start, to_world(touch.position())) {
+            swipes.push(end - start);
+        }
+    }
+
+    for swipe in swipes {
+        if swipe.length() < settings.swipe_min_distance {
+            continue;
+        }
+
+        let (along, across) = (
+            swipe.x.abs().max(swipe.y.abs()),
+            swipe.x.abs().min(swipe.y.abs()),
+        );
+        if across.atan2(along).to_degrees() > settings.swipe_angle_tolerance {
+            continue;
+        }
+
+        action_writer.send(ActionEvent(Action::from_shift(nearest_shift(swipe))));
+    }
+}
+
+/// Binds the next key pressed to the action being rebound. Escape cancels,
+/// or closes the screen when nothing is being rebound.
+fn capture_binding(
+    mut commands: Commands,
+    input: Res<ButtonInput<KeyCode>>,
+    mut keys: ResMut<KeyBindings>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    let Some(action) = rebinding.capturing else {
+        if input.just_pressed(KeyCode::Escape) {
+            commands.remove_resource::<Rebinding>();
+        }
+        return;
+    };
+    let Some(key) = input
+        .get_just_pressed()
+        .copied()
+        .find(|key| !is_modifier(*key))
+    else {
+        return;
+    };
+
+    if key == KeyCode::Escape {
+        rebinding.capturing = None;
+        rebinding.message.clear();
+        return;
+    }
+
+    let (ctrl, shift) = held(&input);
+    let binding = Binding { key, ctrl, shift };
+    match keys.bind(action, binding) {
+        Ok(()) => {
+            rebinding.capturing = None;
+            rebinding.message.clear();
+        }
+        Err(other) => {
+            rebinding.message = format!("{binding} is already used for {}", other.label());
+        }
+    }
+}
+
+/// Handles the actions that aren't tied to a particular screen.
+fn game_actions(
+    mut action_reader: EventReader<ActionEvent>,
+    run_state: Res<State<RunState>>,
+    mut next_state: ResMut<NextState<RunState>>,
+    mut confirm_writer: EventWriter<ConfirmRequest>,
+    settings_open: Option<Res<SettingsOpen>>,
+    mut commands: Commands,
+) {
+    for ActionEvent(action) in action_reader.read() {
+        match (action, run_state.get()) {
+            // Backs out of the settings screen before leaving the pause menu
+            (Action::Pause, _) if settings_open.is_some() => {
+                commands.remove_resource::<SettingsOpen>();
+            }
+            (Action::Restart, RunState::Replay) => {}
+            (Action::Restart, _) => {
+                confirm_writer.send(ConfirmRequest(Confirmable::Restart));
+            }
+            (Action::Pause, RunState::Playing) => next_state.set(RunState::Paused),
+            (Action::Pause, RunState::Paused) => next_state.set(RunState::Playing),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_have_no_conflicts() {
+        for preset in Preset::ALL {
+            assert_eq!(KeyBindings::preset(preset).conflicts(), []);
+        }
+    }
+
+    #[test]
+    fn bind_replaces_an_actions_keys() {
+        let mut keys = KeyBindings::default();
+        let binding = Binding::key(KeyCode::KeyQ);
+
+        assert_eq!(keys.bind(Action::Restart, binding), Ok(()));
+        assert_eq!(keys.bindings(Action::Restart), [binding]);
+        assert_eq!(keys.action_for(Binding::key(KeyCode::KeyR)), None);
+        // Binding the same key again is fine
+        assert_eq!(keys.bind(Action::Restart, binding), Ok(()));
+    }
+
+    #[test]
+    fn bind_refuses_a_key_another_action_uses() {
+        let mut keys = KeyBindings::default();
+
+        assert_eq!(
+            keys.bind(Action::Restart, Binding::key(KeyCode::ArrowLeft)),
+            Err(Action::MoveLeft)
+        );
+        assert_eq!(
+            keys.bindings(Action::Restart),
+            [Binding::key(KeyCode::KeyR)]
+        );
+    }
+
+    #[test]
+    fn conflicts_name_both_actions() {
+        let mut keys = KeyBindings::default();
+        keys.0
+            .insert(Action::Hint, vec![Binding::key(KeyCode::KeyR)]);
+
+        assert_eq!(
+            keys.conflicts(),
+            [(Binding::key(KeyCode::KeyR), Action::Restart, Action::Hint)]
+        );
+    }
+
+    #[test]
+    fn fill_missing_skips_keys_already_taken() {
+        let mut keys = KeyBindings::default();
+        keys.0.remove(&Action::Undo);
+        keys.0.remove(&Action::Hint);
+        keys.bind(Action::Restart, Binding::key(KeyCode::KeyU))
+            .unwrap();
+
+        keys.fill_missing();
+
+        assert_eq!(keys.bindings(Action::Undo), [Binding::ctrl(KeyCode::KeyZ)]);
+        assert_eq!(keys.bindings(Action::Hint), [Binding::key(KeyCode::Slash)]);
+        assert_eq!(keys.conflicts(), []);
+    }
+}
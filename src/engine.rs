@@ -0,0 +1,410 @@
+//! The 2048 rules, independent of Bevy.
+//!
+//! A [`Grid`] knows how to slide and merge tiles, where new tiles may be
+//! placed and whether any move is left. The systems in `main.rs` only
+//! translate between this and the tile entities on screen.
+
+use rand::{seq::IteratorRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BoardShift {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// How many tiles are on the board when a game starts.
+pub const STARTING_TILES: usize = 2;
+
+/// The values new tiles are drawn from, each with a relative weight.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<(u32, u32)>", into = "Vec<(u32, u32)>")]
+pub struct SpawnDistribution {
+    weights: Vec<(u32, u32)>,
+    total: u32,
+}
+
+impl SpawnDistribution {
+    /// Builds a distribution from `(value, weight)` pairs. Returns `None`
+    /// when there is nothing to draw from, i.e. every weight is zero.
+    pub fn new(weights: Vec<(u32, u32)>) -> Option<Self> {
+        let total = weights.iter().map(|(_, weight)| weight).sum();
+        if total == 0 {
+            return None;
+        }
+
+        Some(SpawnDistribution { weights, total })
+    }
+
+    pub fn weights(&self) -> &[(u32, u32)] {
+        &self.weights
+    }
+
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> u32 {
+        let mut roll = rng.gen_range(0..self.total);
+        for (value, weight) in self.weights.iter() {
+            if roll < *weight {
+                return *value;
+            }
+            roll -= weight;
+        }
+
+        unreachable!("roll is always below the total weight")
+    }
+}
+
+impl TryFrom<Vec<(u32, u32)>> for SpawnDistribution {
+    type Error = &'static str;
+
+    fn try_from(weights: Vec<(u32, u32)>) -> Result<Self, Self::Error> {
+        SpawnDistribution::new(weights).ok_or("spawn weights can't all be zero")
+    }
+}
+
+impl From<SpawnDistribution> for Vec<(u32, u32)> {
+    fn from(spawns: SpawnDistribution) -> Self {
+        spawns.weights
+    }
+}
+
+impl Default for SpawnDistribution {
+    /// The classic rules: a 2 nine times out of ten, otherwise a 4.
+    fn default() -> Self {
+        SpawnDistribution::new(vec![(2, 9), (4, 1)]).expect("weights are not all zero")
+    }
+}
+
+/// A single tile that changed cell during a move.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TileMove {
+    pub from: (u8, u8),
+    pub to: (u8, u8),
+    /// The tile was consumed by the tile already sitting at `to`.
+    pub merged: bool,
+}
+
+/// Two tiles combined into one at `position`, now worth `value`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Merge {
+    pub position: (u8, u8),
+    pub value: u32,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MoveOutcome {
+    pub moves: Vec<TileMove>,
+    pub merges: Vec<Merge>,
+    /// Points earned by the move, the sum of all merged values.
+    pub score: u32,
+}
+
+impl MoveOutcome {
+    /// Whether the move did anything at all.
+    pub fn changed(&self) -> bool {
+        !self.moves.is_empty()
+    }
+}
+
+/// The board contents, stored column-major with `(0, 0)` at the bottom left.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "GridCells")]
+pub struct Grid {
+    width: u8,
+    height: u8,
+    cells: Vec<Option<u32>>,
+}
+
+/// A deserialised [`Grid`] that hasn't been checked for the right number of
+/// cells yet.
+#[derive(Deserialize)]
+struct GridCells {
+    width: u8,
+    height: u8,
+    cells: Vec<Option<u32>>,
+}
+
+impl TryFrom<GridCells> for Grid {
+    type Error = String;
+
+    fn try_from(value: GridCells) -> Result<Self, Self::Error> {
+        let expected = usize::from(value.width) * usize::from(value.height);
+        if value.cells.len() != expected {
+            return Err(format!(
+                "a {}x{} grid needs {expected} cells, found {}",
+                value.width,
+                value.height,
+                value.cells.len()
+            ));
+        }
+
+        Ok(Grid {
+            width: value.width,
+            height: value.height,
+            cells: value.cells,
+        })
+    }
+}
+
+impl Grid {
+    pub fn new(width: u8, height: u8) -> Self {
+        Grid {
+            width,
+            height,
+            cells: vec![None; usize::from(width) * usize::from(height)],
+        }
+    }
+
+    pub fn width(&self) -> u8 {
+        self.width
+    }
+
+    pub fn height(&self) -> u8 {
+        self.height
+    }
+
+    fn index(&self, (x, y): (u8, u8)) -> usize {
+        usize::from(x) * usize::from(self.height) + usize::from(y)
+    }
+
+    pub fn get(&self, pos: (u8, u8)) -> Option<u32> {
+        self.cells[self.index(pos)]
+    }
+
+    pub fn set(&mut self, pos: (u8, u8), value: Option<u32>) {
+        let idx = self.index(pos);
+        self.cells[idx] = value;
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.fill(None);
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = (u8, u8)> {
+        let height = self.height;
+        (0..self.width).flat_map(move |x| (0..height).map(move |y| (x, y)))
+    }
+
+    /// Every occupied cell with its value.
+    pub fn tiles(&self) -> impl Iterator<Item = ((u8, u8), u32)> + '_ {
+        self.positions()
+            .filter_map(|pos| self.get(pos).map(|value| (pos, value)))
+    }
+
+    pub fn empty_cells(&self) -> impl Iterator<Item = (u8, u8)> + '_ {
+        self.positions().filter(|pos| self.get(*pos).is_none())
+    }
+
+    pub fn max_tile(&self) -> Option<u32> {
+        self.cells.iter().flatten().copied().max()
+    }
+
+    /// The cells of every row (or column) in the order tiles travel through
+    /// them, starting at the edge the tiles are pushed against.
+    fn lines(&self, shift: BoardShift) -> Vec<Vec<(u8, u8)>> {
+        let (width, height) = (self.width, self.height);
+        match shift {
+            BoardShift::Left => (0..height)
+                .map(|y| (0..width).map(|x| (x, y)).collect())
+                .collect(),
+            BoardShift::Right => (0..height)
+                .map(|y| (0..width).rev().map(|x| (x, y)).collect())
+                .collect(),
+            BoardShift::Up => (0..width)
+                .map(|x| (0..height).rev().map(|y| (x, y)).collect())
+                .collect(),
+            BoardShift::Down => (0..width)
+                .map(|x| (0..height).map(|y| (x, y)).collect())
+                .collect(),
+        }
+    }
+
+    /// Slides every tile towards `shift`, merging equal neighbours once.
+    pub fn apply_move(&mut self, shift: BoardShift) -> MoveOutcome {
+        let mut outcome = MoveOutcome::default();
+
+        for line in self.lines(shift) {
+            // Index into `line` of the next free cell, and whether the tile
+            // placed just before it can still take a merge.
+            let mut target = 0;
+            let mut can_merge = false;
+
+            for idx in 0..line.len() {
+                let from = line[idx];
+                let Some(value) = self.get(from) else {
+                    continue;
+                };
+
+                if can_merge && self.get(line[target - 1]) == Some(value) {
+                    // Merge
+                    let to = line[target - 1];
+                    let merged_value = value * 2;
+                    self.set(from, None);
+                    self.set(to, Some(merged_value));
+                    outcome.moves.push(TileMove {
+                        from,
+                        to,
+                        merged: true,
+                    });
+                    outcome.merges.push(Merge {
+                        position: to,
+                        value: merged_value,
+                    });
+                    outcome.score += merged_value;
+                    can_merge = false;
+                } else {
+                    let to = line[target];
+                    if to != from {
+                        self.set(from, None);
+                        self.set(to, Some(value));
+                        outcome.moves.push(TileMove {
+                            from,
+                            to,
+                            merged: false,
+                        });
+                    }
+                    target += 1;
+                    can_merge = true;
+                }
+            }
+        }
+
+        outcome
+    }
+
+    /// Places a tile drawn from `spawns` on a random empty cell, if there is
+    /// one, returning where it went and its value.
+    pub fn spawn_random<R: Rng + ?Sized>(
+        &mut self,
+        rng: &mut R,
+        spawns: &SpawnDistribution,
+    ) -> Option<((u8, u8), u32)> {
+        let pos = self.empty_cells().choose(rng)?;
+        let value = spawns.sample(rng);
+        self.set(pos, Some(value));
+        Some((pos, value))
+    }
+
+    /// No empty cell is left and no two neighbours can merge.
+    pub fn is_game_over(&self) -> bool {
+        if self.empty_cells().next().is_some() {
+            return false;
+        }
+
+        !self.tiles().any(|((x, y), value)| {
+            let right = x + 1 < self.width && self.get((x + 1, y)) == Some(value);
+            let above = y + 1 < self.height && self.get((x, y + 1)) == Some(value);
+            right || above
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    /// A single row, read left to right.
+    fn row(values: &[Option<u32>]) -> Grid {
+        let mut grid = Grid::new(values.len() as u8, 1);
+        for (x, value) in values.iter().enumerate() {
+            grid.set((x as u8, 0), *value);
+        }
+        grid
+    }
+
+    fn values(grid: &Grid) -> Vec<Option<u32>> {
+        (0..grid.width()).map(|x| grid.get((x, 0))).collect()
+    }
+
+    #[test]
+    fn each_tile_merges_once_per_move() {
+        let mut grid = row(&[Some(2), Some(2), Some(2), Some(2)]);
+        let outcome = grid.apply_move(BoardShift::Left);
+
+        assert_eq!(values(&grid), [Some(4), Some(4), None, None]);
+        assert_eq!(outcome.merges.len(), 2);
+        assert_eq!(outcome.score, 8);
+    }
+
+    #[test]
+    fn merged_tiles_do_not_merge_again() {
+        let mut grid = row(&[Some(4), Some(4), Some(8), None]);
+        let outcome = grid.apply_move(BoardShift::Left);
+
+        assert_eq!(values(&grid), [Some(8), Some(8), None, None]);
+        assert_eq!(
+            outcome.merges,
+            [Merge {
+                position: (0, 0),
+                value: 8
+            }]
+        );
+    }
+
+    #[test]
+    fn moves_towards_the_shifted_edge() {
+        let mut grid = row(&[Some(2), None, Some(2), Some(4)]);
+        let outcome = grid.apply_move(BoardShift::Right);
+
+        assert_eq!(values(&grid), [None, None, Some(4), Some(4)]);
+        assert_eq!(outcome.score, 4);
+        assert!(outcome.changed());
+    }
+
+    #[test]
+    fn blocked_move_changes_nothing() {
+        let mut grid = row(&[Some(2), Some(4), None, None]);
+        let outcome = grid.apply_move(BoardShift::Left);
+
+        assert_eq!(values(&grid), [Some(2), Some(4), None, None]);
+        assert!(!outcome.changed());
+        assert_eq!(outcome, MoveOutcome::default());
+    }
+
+    #[test]
+    fn game_over_needs_a_full_board_without_merges() {
+        let mut grid = Grid::new(2, 2);
+        grid.set((0, 0), Some(2));
+        grid.set((1, 0), Some(4));
+        grid.set((0, 1), Some(4));
+        assert!(!grid.is_game_over());
+
+        grid.set((1, 1), Some(2));
+        assert!(grid.is_game_over());
+
+        grid.set((1, 1), Some(4));
+        assert!(!grid.is_game_over());
+    }
+
+    #[test]
+    fn spawns_are_repeatable_for_a_seed() {
+        let spawns = SpawnDistribution::default();
+        let play = |seed| {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            let mut grid = Grid::new(4, 4);
+            let placed: Vec<_> = (0..8)
+                .map(|_| grid.spawn_random(&mut rng, &spawns))
+                .collect();
+            (grid, placed)
+        };
+
+        let (grid, placed) = play(7);
+        assert_eq!(play(7), (grid.clone(), placed.clone()));
+        assert_eq!(grid.empty_cells().count(), 8);
+        assert!(placed.iter().all(|spawn| spawn.is_some()));
+    }
+
+    #[test]
+    fn spawning_on_a_full_board_places_nothing() {
+        let mut grid = row(&[Some(2), Some(4)]);
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+
+        assert_eq!(
+            grid.spawn_random(&mut rng, &SpawnDistribution::default()),
+            None
+        );
+    }
+}
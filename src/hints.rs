@@ -0,0 +1,239 @@
+//! Hints, asked for with the Hint key (/).
+//!
+//! A hint searches the current board for as long as `ai.hint_ms` allows,
+//! points an arrow across the board in the best direction and puts each
+//! direction's score beside the edge it moves towards. Every hint is counted
+//! in [`Game::hints_used`].
+
+use crate::{
+    colours,
+    controls::{Action, ActionEvent},
+    AiSettings, Board, BoardShiftedEvent, FontSpec, Game, GridRestoredEvent, NewGameEvent,
+    RunState, Settings, TileSpawns,
+};
+use bevy::prelude::*;
+use std::{
+    f32::consts::{FRAC_1_SQRT_2, PI},
+    time::Duration,
+};
+use two_oh_four_eight::{
+    ai::{self, LOST, SHIFTS},
+    engine::{BoardShift, SpawnDistribution},
+};
+
+pub struct HintPlugin;
+
+impl Plugin for HintPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                clear_hint.run_if(
+                    on_event::<BoardShiftedEvent>()
+                        .or_else(on_event::<GridRestoredEvent>())
+                        .or_else(on_event::<NewGameEvent>()),
+                ),
+                show_hint.run_if(in_state(RunState::Playing)),
+            )
+                .chain(),
+        );
+    }
+}
+
+/// The arrow and scores of the hint on screen, children of the [`Board`].
+#[derive(Component)]
+struct HintOverlay;
+
+fn clear_hint(mut commands: Commands, query_hint: Query<Entity, With<HintOverlay>>) {
+    for entity in query_hint.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Each move's score, best first, counting the hint. `None`, and not counted,
+/// when no move is left to suggest.
+fn suggest(
+    game: &mut Game,
+    spawns: &SpawnDistribution,
+    ai: &AiSettings,
+) -> Option<Vec<(BoardShift, f32)>> {
+    let scores = ai::timed_scores(
+        &game.grid,
+        spawns,
+        &ai.heuristics,
+        Duration::from_millis(ai.hint_ms),
+        ai.hint_max_depth,
+    );
+    if scores.is_empty() {
+        return None;
+    }
+
+    game.hints_used += 1;
+    Some(scores)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn show_hint(
+    mut commands: Commands,
+    mut action_reader: EventReader<ActionEvent>,
+    mut game: ResMut<Game>,
+    spawns: Res<TileSpawns>,
+    settings: Res<Settings>,
+    query_board: Query<(Entity, &Board)>,
+    query_hint: Query<Entity, With<HintOverlay>>,
+    font_spec: Res<FontSpec>,
+) {
+    let asked = action_reader
+        .read()
+        .filter(|ActionEvent(action)| *action == Action::Hint)
+        .count();
+    if asked == 0 {
+        return;
+    }
+
+    let Some(scores) = suggest(&mut game, &spawns, &settings.ai) else {
+        return;
+    };
+    let (best, _) = scores[0];
+
+    for entity in query_hint.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let (board_entity, board) = query_board.single();
+    commands.entity(board_entity).with_children(|builder| {
+        spawn_arrow(builder, board, best);
+
+        let half = board.sprite_size() / 2.0;
+        for shift in SHIFTS {
+            let label = match scores.iter().find(|(scored, _)| *scored == shift) {
+                None => "-".to_string(),
+                Some((_, score)) if *score <= LOST => "lost".to_string(),
+                Some((_, score)) => format!("{score:.1}"),
+            };
+            let (offset, colour) = (
+                direction(shift) * (half + Vec2::new(32.0, 16.0)),
+                if shift == best {
+                    colours::HINT
+                } else {
+                    Color::WHITE
+                },
+            );
+
+            builder.spawn((
+                Text2dBundle {
+                    text: Text::from_section(
+                        label,
+                        TextStyle {
+                            font: font_spec.family.clone(),
+                            font_size: 16.0,
+                            color: colour,
+                        },
+                    ),
+                    transform: Transform::from_translation(offset.extend(10.0)),
+                    ..default()
+                },
+                HintOverlay,
+            ));
+        }
+    });
+}
+
+/// A unit vector pointing the way `shift` moves the tiles.
+fn direction(shift: BoardShift) -> Vec2 {
+    match shift {
+        BoardShift::Left => Vec2::NEG_X,
+        BoardShift::Right => Vec2::X,
+        BoardShift::Up => Vec2::Y,
+        BoardShift::Down => Vec2::NEG_Y,
+    }
+}
+
+/// An arrow through the middle of the board, drawn pointing right and then
+/// turned to face `shift`: a shaft with a diamond for its head.
+fn spawn_arrow(builder: &mut ChildBuilder, board: &Board, shift: BoardShift) {
+    let length = board.sprite_size().min_element() * 0.5;
+    let thickness = board.tile_sprite_size().x * 0.3;
+    let head = board.tile_sprite_size().x * 0.6;
+    let angle = direction(shift).y.atan2(direction(shift).x);
+
+    // The shaft runs from the tail to just inside the head, where the head
+    // is as wide as the shaft, so the two barely overlap
+    let tip = length / 2.0;
+    let head_centre = tip - head * FRAC_1_SQRT_2;
+    let shaft_end = head_centre - head * FRAC_1_SQRT_2 + thickness / 2.0;
+    let shaft_length = shaft_end + length / 2.0;
+
+    builder
+        .spawn((
+            SpatialBundle::from_transform(
+                Transform::from_xyz(0.0, 0.0, 10.0).with_rotation(Quat::from_rotation_z(angle)),
+            ),
+            HintOverlay,
+        ))
+        .with_children(|arrow| {
+            arrow.spawn(SpriteBundle {
+                sprite: Sprite {
+                    color: colours::HINT,
+                    custom_size: Some(Vec2::new(shaft_length, thickness)),
+                    ..default()
+                },
+                transform: Transform::from_xyz(shaft_end - shaft_length / 2.0, 0.0, 0.0),
+                ..default()
+            });
+            arrow.spawn(SpriteBundle {
+                sprite: Sprite {
+                    color: colours::HINT,
+                    custom_size: Some(Vec2::splat(head)),
+                    ..default()
+                },
+                transform: Transform::from_xyz(head_centre, 0.0, 0.0)
+                    .with_rotation(Quat::from_rotation_z(PI / 4.0)),
+                ..default()
+            });
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use two_oh_four_eight::engine::Grid;
+
+    #[test]
+    fn a_hint_is_counted_when_there_is_a_move() {
+        let mut game = Game {
+            grid: Grid::new(4, 4),
+            ..Game::default()
+        };
+        game.grid.set((0, 0), Some(2));
+        game.grid.set((1, 0), Some(2));
+
+        let scores = suggest(
+            &mut game,
+            &SpawnDistribution::default(),
+            &AiSettings::default(),
+        );
+        assert!(scores.is_some_and(|scores| !scores.is_empty()));
+        assert_eq!(game.hints_used, 1);
+    }
+
+    #[test]
+    fn no_hint_is_counted_when_the_game_is_over() {
+        let mut game = Game {
+            grid: Grid::new(2, 2),
+            hints_used: 3,
+            ..Game::default()
+        };
+        for (pos, value) in [((0, 0), 2), ((1, 0), 4), ((0, 1), 4), ((1, 1), 2)] {
+            game.grid.set(pos, Some(value));
+        }
+
+        let scores = suggest(
+            &mut game,
+            &SpawnDistribution::default(),
+            &AiSettings::default(),
+        );
+        assert!(scores.is_none());
+        assert_eq!(game.hints_used, 3);
+    }
+}
@@ -0,0 +1,202 @@
+//! Undo and redo of moves.
+//!
+//! `board_shift` records a [`Snapshot`] before every move that changes the
+//! board. Undoing swaps the current state for the last snapshot and the tiles
+//! on screen are rebuilt from the restored grid.
+
+use crate::{
+    controls::{Action, ActionEvent},
+    resync_tiles, Game, GameRng, GridRestoredEvent, NewGameEvent, RunState, Settings,
+};
+use bevy::prelude::*;
+use std::collections::VecDeque;
+use two_oh_four_eight::engine::Grid;
+
+pub struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<History>()
+            .add_event::<HistoryEvent>()
+            .add_systems(
+                Update,
+                (
+                    clear_history.run_if(on_event::<NewGameEvent>()),
+                    (history_input, apply_history)
+                        .chain()
+                        .run_if(in_state(RunState::Playing).or_else(in_state(RunState::GameOver))),
+                )
+                    .chain()
+                    .before(resync_tiles),
+            );
+    }
+}
+
+/// The state of a game between two moves.
+#[derive(Clone)]
+pub struct Snapshot {
+    grid: Grid,
+    score: u32,
+    moves: u32,
+    won: bool,
+    rng: GameRng,
+}
+
+impl Snapshot {
+    pub fn capture(game: &Game, rng: &GameRng) -> Self {
+        Snapshot {
+            grid: game.grid.clone(),
+            score: game.score,
+            moves: game.moves,
+            won: game.won,
+            rng: rng.clone(),
+        }
+    }
+
+    fn restore(self, game: &mut Game, rng: &mut GameRng) {
+        game.grid = self.grid;
+        game.score = self.score;
+        game.moves = self.moves;
+        game.won = self.won;
+        *rng = self.rng;
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct History {
+    undo: VecDeque<Snapshot>,
+    redo: Vec<Snapshot>,
+}
+
+impl History {
+    /// Remembers the state from before a move, dropping the oldest snapshot
+    /// once there are more than `limit`. A new move can't be redone past, so
+    /// this also forgets anything undone.
+    pub fn record(&mut self, snapshot: Snapshot, limit: usize) {
+        self.redo.clear();
+        self.push_undo(snapshot, limit);
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    fn push_undo(&mut self, snapshot: Snapshot, limit: usize) {
+        self.undo.push_back(snapshot);
+        while self.undo.len() > limit {
+            self.undo.pop_front();
+        }
+    }
+}
+
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryEvent {
+    Undo,
+    Redo,
+}
+
+fn history_input(
+    mut action_reader: EventReader<ActionEvent>,
+    mut history_writer: EventWriter<HistoryEvent>,
+) {
+    for ActionEvent(action) in action_reader.read() {
+        match action {
+            Action::Undo => history_writer.send(HistoryEvent::Undo),
+            Action::Redo => history_writer.send(HistoryEvent::Redo),
+            _ => continue,
+        };
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn apply_history(
+    mut history_reader: EventReader<HistoryEvent>,
+    mut history: ResMut<History>,
+    mut game: ResMut<Game>,
+    mut rng: ResMut<GameRng>,
+    settings: Res<Settings>,
+    run_state: Res<State<RunState>>,
+    mut next_state: ResMut<NextState<RunState>>,
+    mut restored: EventWriter<GridRestoredEvent>,
+) {
+    let mut changed = false;
+
+    for event in history_reader.read() {
+        let current = Snapshot::capture(&game, &rng);
+        match event {
+            HistoryEvent::Undo => {
+                if let Some(previous) = history.undo.pop_back() {
+                    history.redo.push(current);
+                    previous.restore(&mut game, &mut rng);
+                    changed = true;
+                }
+            }
+            HistoryEvent::Redo => {
+                if let Some(next) = history.redo.pop() {
+                    history.push_undo(current, settings.undo_limit);
+                    next.restore(&mut game, &mut rng);
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    if changed {
+        restored.send(GridRestoredEvent);
+        if *run_state.get() == RunState::GameOver {
+            next_state.set(RunState::Playing);
+        }
+    }
+}
+
+fn clear_history(mut history: ResMut<History>) {
+    history.undo.clear();
+    history.redo.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(moves: u32) -> Snapshot {
+        let game = Game {
+            moves,
+            ..Game::default()
+        };
+        Snapshot::capture(&game, &GameRng::default())
+    }
+
+    fn undo_moves(history: &History) -> Vec<u32> {
+        history.undo.iter().map(|snapshot| snapshot.moves).collect()
+    }
+
+    #[test]
+    fn keeps_the_latest_snapshots_up_to_the_limit() {
+        let mut history = History::default();
+        for moves in 0..5 {
+            history.record(snapshot(moves), 3);
+        }
+
+        assert_eq!(undo_moves(&history), [2, 3, 4]);
+    }
+
+    #[test]
+    fn a_new_move_forgets_what_was_undone() {
+        let mut history = History::default();
+        history.record(snapshot(0), 10);
+        history.redo.push(snapshot(1));
+
+        history.record(snapshot(1), 10);
+        assert!(history.redo.is_empty());
+        assert_eq!(undo_moves(&history), [0, 1]);
+    }
+
+    #[test]
+    fn a_limit_of_zero_keeps_nothing() {
+        let mut history = History::default();
+        history.record(snapshot(0), 0);
+        history.record(snapshot(1), 0);
+
+        assert!(!history.can_undo());
+    }
+}
@@ -0,0 +1,7 @@
+//! Game logic that does not depend on Bevy, shared by the game binary and
+//! anything else that wants to play by the same rules.
+
+pub mod ai;
+pub mod engine;
+pub mod replay;
+pub mod simulation;
@@ -1,13 +1,72 @@
+mod autoplay;
+mod cli;
 mod colours;
+mod controls;
+mod hints;
+mod history;
+mod pause;
+mod persistence;
+mod popups;
+mod recorder;
+mod simulate;
+mod tui;
 mod ui;
 
 use bevy::prelude::*;
 use bevy_easings::*;
+use cli::Cli;
+use controls::ActionEvent;
+use history::{History, Snapshot};
 use itertools::Itertools;
-use rand::prelude::IteratorRandom;
-use std::{cmp::Ordering, collections::HashMap, ops::Range};
+use persistence::{Profile, SavedGame};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use recorder::{Recorder, ReplayPlayer};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    ops::RangeInclusive,
+    time::Duration,
+};
+use two_oh_four_eight::{
+    ai::Heuristics,
+    engine::{BoardShift, Grid, Merge, SpawnDistribution, STARTING_TILES},
+};
 
 fn main() {
+    let cli = match Cli::parse() {
+        Ok(cli) => cli,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(2);
+        }
+    };
+
+    if let Some(simulate) = &cli.simulate {
+        if let Err(err) = simulate::run(simulate) {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if cli.tui {
+        if let Err(err) = tui::run(&cli) {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let replay = match cli.replay.as_deref().map(recorder::load_replay) {
+        Some(Ok(replay)) => Some(replay),
+        Some(Err(err)) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+        None => None,
+    };
+
     App::new()
         .insert_resource(ClearColor(Color::hex("1f2638").unwrap()))
         .add_plugins(DefaultPlugins.set(WindowPlugin {
@@ -19,23 +78,62 @@ fn main() {
         }))
         .add_plugins(EasingsPlugin)
         .add_plugins(ui::GameUIPlugin)
+        .add_plugins(controls::ControlsPlugin)
+        .add_plugins(autoplay::AutoplayPlugin)
+        .add_plugins(hints::HintPlugin)
+        .add_plugins(popups::ScorePopupPlugin)
+        .add_plugins(pause::PausePlugin)
+        .add_plugins(persistence::PersistencePlugin)
+        .add_plugins(history::HistoryPlugin)
+        .add_plugins(recorder::ReplayPlugin { replay })
+        .insert_resource(cli)
         .init_resource::<FontSpec>()
         .init_resource::<Game>()
+        .init_resource::<TileSpawns>()
+        .init_resource::<GameRng>()
+        .init_resource::<Settings>()
+        .init_resource::<MoveQueue>()
         .init_state::<RunState>()
-        .add_systems(Startup, (setup, spawn_board, apply_deferred).chain())
-        .add_systems(OnEnter(RunState::Playing), (game_reset, spawn_tiles))
+        .add_systems(
+            Startup,
+            (setup, restore_game, spawn_board, apply_deferred).chain(),
+        )
+        .add_systems(OnExit(RunState::Playing), clear_moves)
         .add_systems(
             Update,
             (
-                render_tile_points,
-                board_shift,
-                render_tiles,
-                new_tile_handler,
-                end_game,
+                rebuild_board,
+                (game_reset, spawn_tiles)
+                    .chain()
+                    .run_if(on_event::<NewGameEvent>()),
+                resync_tiles.run_if(on_event::<GridRestoredEvent>()),
+                clear_moves
+                    .run_if(on_event::<NewGameEvent>().or_else(on_event::<GridRestoredEvent>())),
+                (queue_moves, dispatch_moves)
+                    .chain()
+                    .run_if(in_state(RunState::Playing)),
+                (
+                    render_tile_points,
+                    // A move's new tile spawns in the same frame, straight
+                    // after it
+                    (board_shift, update_score, render_tiles, new_tile_handler).chain(),
+                    shake_board,
+                )
+                    .run_if(in_state(RunState::Playing).or_else(in_state(RunState::Replay))),
+                despawn_consumed,
+                (end_game, win_game)
+                    .chain()
+                    .run_if(in_state(RunState::Playing)),
             )
-                .run_if(in_state(RunState::Playing)),
+                .chain(),
         )
+        .add_event::<ShiftRequestEvent>()
+        .add_event::<BoardShiftedEvent>()
+        .add_event::<ScoreChanged>()
         .add_event::<NewTileEvent>()
+        .add_event::<NoOpMoveEvent>()
+        .add_event::<NewGameEvent>()
+        .add_event::<GridRestoredEvent>()
         .run()
 }
 
@@ -63,8 +161,283 @@ impl FromWorld for FontSpec {
 struct Game {
     score: u32,
     score_best: u32,
+    moves: u32,
+    /// What the RNG was seeded with at the start of this game.
+    seed: u64,
+    /// The win target has been reached this game, so it isn't celebrated again.
+    won: bool,
+    /// How many hints were asked for, so a game played with help can be
+    /// told apart from one played without.
+    hints_used: u32,
+    /// The score has gone past the best that stood when this game started.
+    new_best: bool,
+    grid: Grid,
+}
+
+impl Game {
+    /// A fresh game on an empty board of `size`, seeded from `--seed`, then
+    /// the seed in settings, then entropy. Spawning the starting tiles is
+    /// left to the caller.
+    fn new_seeded(
+        (width, height): (u8, u8),
+        cli_seed: Option<u64>,
+        settings: &Settings,
+        profile: &Profile,
+    ) -> (Game, GameRng) {
+        let seed = cli_seed.or(settings.seed).unwrap_or_else(rand::random);
+        let game = Game {
+            score_best: profile.best_for((width, height)),
+            seed,
+            grid: Grid::new(width, height),
+            ..Game::default()
+        };
+
+        (game, GameRng(ChaCha8Rng::seed_from_u64(seed)))
+    }
+
+    /// Picks up `saved` where it was left: the game, its RNG and recording,
+    /// and the state to carry on in. The board size setting follows the
+    /// saved game.
+    fn from_saved(
+        saved: SavedGame,
+        settings: &mut Settings,
+        profile: &Profile,
+        recorder: &mut Recorder,
+    ) -> (Game, GameRng, RunState) {
+        settings.board_width = saved.grid.width();
+        settings.board_height = saved.grid.height();
+
+        let game = Game {
+            score: saved.score,
+            score_best: profile.best_for(settings.board_size()),
+            moves: saved.moves,
+            seed: saved.seed,
+            won: saved.won,
+            hints_used: saved.hints_used,
+            new_best: saved.new_best,
+            grid: saved.grid,
+        };
+        if let Some(replay) = saved.replay {
+            recorder.start(replay);
+            // A finished game's recording was saved when it ended
+            if saved.run_state == RunState::GameOver {
+                recorder.mark_saved();
+            }
+        }
+        // A paused game comes back running, and a replay was never the
+        // player's own game to carry on with
+        let run_state = match saved.run_state {
+            RunState::Paused | RunState::Replay => RunState::Playing,
+            state => state,
+        };
+
+        (game, GameRng(saved.rng), run_state)
+    }
+
+    /// Adds points to the score, carrying the best score along with it.
+    fn add_score(&mut self, points: u32) {
+        self.score += points;
+        if self.score > self.score_best {
+            self.score_best = self.score;
+            self.new_best = true;
+        }
+    }
+}
+
+/// How many cells a board can have along either side.
+const BOARD_SIZE_RANGE: RangeInclusive<u8> = 3..=8;
+
+/// The board sizes that can be picked from the UI, cycled through in order.
+const BOARD_SIZES: [(u8, u8); 9] = [
+    (3, 3),
+    (4, 4),
+    (5, 5),
+    (6, 6),
+    (7, 7),
+    (8, 8),
+    (3, 5),
+    (4, 6),
+    (6, 8),
+];
+
+#[derive(Resource, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct Settings {
+    /// The tile value that wins the game.
+    win_target: u32,
+    board_width: u8,
+    board_height: u8,
+    /// Seeds every new game when set, instead of a random seed.
+    seed: Option<u64>,
+    /// How many moves can be taken back. Zero turns undo off entirely, for
+    /// competitive play.
+    undo_limit: usize,
+    /// How many moves pressed while tiles are still animating are kept to
+    /// be played once the board settles. One is always kept.
+    move_buffer: usize,
+    /// Cuts running animations short when another move is waiting, rather
+    /// than letting them finish first.
+    fast_forward: bool,
+    /// How far the left stick has to be pushed, from 0 to 1, to make a move.
+    stick_deadzone: f32,
+    /// How close to the middle the stick has to come back before it can
+    /// make another move.
+    stick_rearm: f32,
+    /// How far, in pixels, a drag or swipe has to go to make a move.
+    swipe_min_distance: f32,
+    /// How many degrees a swipe can stray from straight along a row or
+    /// column and still count.
+    swipe_angle_tolerance: f32,
+    /// Pauses the game when another window takes the focus.
+    pause_on_focus_loss: bool,
+    /// Asks before ending, restarting or resizing a game in progress.
+    confirm_destructive: bool,
+    animations: Animations,
+    ai: AiSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            win_target: 2048,
+            board_width: 4,
+            board_height: 4,
+            seed: None,
+            undo_limit: 50,
+            move_buffer: 4,
+            fast_forward: false,
+            stick_deadzone: 0.5,
+            stick_rearm: 0.25,
+            swipe_min_distance: 30.0,
+            swipe_angle_tolerance: 30.0,
+            pause_on_focus_loss: true,
+            confirm_destructive: true,
+            animations: Animations::default(),
+            ai: AiSettings::default(),
+        }
+    }
+}
+
+/// How the computer player thinks and how fast it plays.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct AiSettings {
+    /// How many moves ahead the computer player searches at most. Each extra
+    /// move makes the search many times slower, especially on big boards.
+    depth: u8,
+    /// How long the computer player may spend on a move, in milliseconds,
+    /// so searching doesn't hold up the frame.
+    move_ms: u64,
+    moves_per_second: f32,
+    /// How long a hint may spend searching, in milliseconds. It searches
+    /// deeper the more time it has, up to `hint_max_depth` moves ahead.
+    hint_ms: u64,
+    hint_max_depth: u8,
+    heuristics: Heuristics,
+}
+
+impl Default for AiSettings {
+    fn default() -> Self {
+        AiSettings {
+            depth: 3,
+            move_ms: 30,
+            moves_per_second: 4.0,
+            hint_ms: 200,
+            hint_max_depth: 6,
+            heuristics: Heuristics::default(),
+        }
+    }
+}
+
+/// How long tiles take to slide, pop when merged and grow in when spawned,
+/// and how long the points scored float before fading out. A duration of
+/// zero skips that animation.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct Animations {
+    /// Turns every animation off, tiles jump straight to where they end up.
+    enabled: bool,
+    slide_ms: u64,
+    pop_ms: u64,
+    spawn_ms: u64,
+    popup_ms: u64,
+}
+
+impl Default for Animations {
+    fn default() -> Self {
+        Animations {
+            enabled: true,
+            slide_ms: 100,
+            pop_ms: 150,
+            spawn_ms: 150,
+            popup_ms: 800,
+        }
+    }
+}
+
+impl Animations {
+    fn duration(&self, ms: u64) -> Option<Duration> {
+        (self.enabled && ms > 0).then(|| Duration::from_millis(ms))
+    }
+
+    fn slide(&self) -> Option<Duration> {
+        self.duration(self.slide_ms)
+    }
+
+    fn pop(&self) -> Option<Duration> {
+        self.duration(self.pop_ms)
+    }
+
+    fn spawn(&self) -> Option<Duration> {
+        self.duration(self.spawn_ms)
+    }
+
+    fn popup(&self) -> Option<Duration> {
+        self.duration(self.popup_ms)
+    }
+}
+
+impl Settings {
+    /// The board dimensions, each kept within [`BOARD_SIZE_RANGE`].
+    fn board_size(&self) -> (u8, u8) {
+        let (min, max) = (*BOARD_SIZE_RANGE.start(), *BOARD_SIZE_RANGE.end());
+        (
+            self.board_width.clamp(min, max),
+            self.board_height.clamp(min, max),
+        )
+    }
+
+    /// Switches to the size after the current one in [`BOARD_SIZES`].
+    fn cycle_board_size(&mut self) {
+        let current = BOARD_SIZES
+            .iter()
+            .position(|size| *size == self.board_size());
+        let (width, height) = match current {
+            Some(idx) => BOARD_SIZES[(idx + 1) % BOARD_SIZES.len()],
+            None => BOARD_SIZES[0],
+        };
+        self.board_width = width;
+        self.board_height = height;
+    }
 }
 
+/// The source of randomness for tile spawns. Kept as a resource, rather than
+/// using `thread_rng`, so its state can be saved along with the game and so
+/// the same seed plays out the same way every time.
+#[derive(Resource, Clone, Deref, DerefMut)]
+struct GameRng(ChaCha8Rng);
+
+impl Default for GameRng {
+    fn default() -> Self {
+        GameRng(ChaCha8Rng::from_entropy())
+    }
+}
+
+/// The values new tiles can take and how likely each one is. Defaults to
+/// 90% 2s and 10% 4s; insert a different one before startup to change it.
+#[derive(Default, Resource, Deref)]
+struct TileSpawns(SpawnDistribution);
+
 #[derive(Component)]
 struct TileText;
 
@@ -81,28 +454,63 @@ struct Position {
 
 #[derive(Component)]
 struct Board {
-    size: u8,
-    size_px: f32,
+    width: u8,
+    height: u8,
+    tile_size: f32,
+    spacer: f32,
+    size_px: Vec2,
 }
 
 impl Board {
-    fn new(size: u8) -> Self {
-        let size_px = f32::from(size) * TILE_SIZE + f32::from(size + 1) * TILE_SPACER;
-        Board { size, size_px }
+    /// Tiles shrink on boards bigger than 4x4 (and grow on smaller ones) so
+    /// the board takes up about the same space whatever its size.
+    fn new(width: u8, height: u8) -> Self {
+        let scale = 4.0 / f32::from(width.max(height));
+        let tile_size = TILE_SIZE * scale;
+        let spacer = TILE_SPACER * scale;
+        let size_px = Vec2::new(
+            f32::from(width) * tile_size + f32::from(width + 1) * spacer,
+            f32::from(height) * tile_size + f32::from(height + 1) * spacer,
+        );
+        Board {
+            width,
+            height,
+            tile_size,
+            spacer,
+            size_px,
+        }
     }
 
     fn sprite_size(&self) -> Vec2 {
-        Vec2::new(self.size_px, self.size_px)
+        self.size_px
     }
 
-    fn cell_position_to_coordinate(&self, pos: u8) -> f32 {
-        let bottom_left = (-self.size_px / 2.0) + (0.5 * TILE_SIZE);
-        bottom_left + (f32::from(pos) * TILE_SIZE) + (f32::from(pos + 1) * TILE_SPACER)
+    fn tile_sprite_size(&self) -> Vec2 {
+        Vec2::new(self.tile_size, self.tile_size)
+    }
+
+    /// Shrinks the text as the number gets longer so it stays inside the tile.
+    fn font_size(&self, value: u32) -> f32 {
+        let digits = value.to_string().len() as f32;
+        self.tile_size.min(self.tile_size * 1.8 / digits)
+    }
+
+    fn cell_position_to_coordinate(&self, x: u8, y: u8) -> Vec2 {
+        let bottom_left = (-self.size_px / 2.0) + (0.5 * self.tile_size);
+        let pos = Vec2::new(f32::from(x), f32::from(y));
+        bottom_left + (pos * self.tile_size) + ((pos + 1.0) * self.spacer)
     }
 }
 
-fn spawn_board(mut commands: Commands) {
-    let board = Board::new(4);
+/// The size of board to show: the replay's while watching one, otherwise
+/// the configured size.
+fn board_size(settings: &Settings, player: Option<&ReplayPlayer>) -> (u8, u8) {
+    player.map_or_else(|| settings.board_size(), ReplayPlayer::board_size)
+}
+
+fn spawn_board(mut commands: Commands, settings: Res<Settings>, player: Option<Res<ReplayPlayer>>) {
+    let (width, height) = board_size(&settings, player.as_deref());
+    let board = Board::new(width, height);
 
     commands
         .spawn(SpriteBundle {
@@ -114,19 +522,18 @@ fn spawn_board(mut commands: Commands) {
             ..default()
         })
         .with_children(|builder| {
-            let tiles = (0..board.size).cartesian_product(0..board.size);
+            let tiles = (0..board.width).cartesian_product(0..board.height);
 
             for tile in tiles {
-                let tile_x = board.cell_position_to_coordinate(tile.0);
-                let tile_y = board.cell_position_to_coordinate(tile.1);
+                let tile_pos = board.cell_position_to_coordinate(tile.0, tile.1);
 
                 builder.spawn(SpriteBundle {
                     sprite: Sprite {
                         color: colours::TILE_PLACEHOLDER,
-                        custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE)),
+                        custom_size: Some(board.tile_sprite_size()),
                         ..default()
                     },
-                    transform: Transform::from_xyz(tile_x, tile_y, 1.0),
+                    transform: Transform::from_translation(tile_pos.extend(1.0)),
                     ..default()
                 });
             }
@@ -134,32 +541,68 @@ fn spawn_board(mut commands: Commands) {
         .insert(board);
 }
 
-fn spawn_tiles(mut commands: Commands, query_board: Query<&Board>, font_spec: Res<FontSpec>) {
-    let board = query_board.single();
+/// Replaces the board, and starts a new game on it, when the configured size
+/// no longer matches the one on screen.
+fn rebuild_board(
+    mut commands: Commands,
+    query_board: Query<(Entity, &Board)>,
+    settings: Res<Settings>,
+    player: Option<Res<ReplayPlayer>>,
+    mut new_game: EventWriter<NewGameEvent>,
+) {
+    let (entity, board) = query_board.single();
+
+    if (board.width, board.height) != board_size(&settings, player.as_deref()) {
+        commands.entity(entity).despawn_recursive();
+        spawn_board(commands, settings, player);
+        new_game.send(NewGameEvent);
+    }
+}
 
-    let mut rng = rand::thread_rng();
-    let starting_tiles: Vec<(u8, u8)> = (0..board.size)
-        .cartesian_product(0..board.size)
-        .choose_multiple(&mut rng, 2);
+fn spawn_tiles(
+    mut commands: Commands,
+    query_board: Query<(Entity, &Board)>,
+    mut game: ResMut<Game>,
+    mut rng: ResMut<GameRng>,
+    spawns: Res<TileSpawns>,
+    font_spec: Res<FontSpec>,
+    settings: Res<Settings>,
+) {
+    let (board_entity, board) = query_board.single();
 
-    for (x, y) in starting_tiles.iter() {
-        let pos = Position { x: *x, y: *y };
-        spawn_tile(&mut commands, board, &font_spec, pos);
+    for _ in 0..STARTING_TILES {
+        if let Some(((x, y), value)) = game.grid.spawn_random(&mut rng.0, &spawns) {
+            let tile = spawn_tile(
+                &mut commands,
+                (board_entity, board),
+                &font_spec,
+                Position { x, y },
+                value,
+            );
+            if let Some(duration) = settings.animations.spawn() {
+                scale_in(&mut commands, board, tile, (x, y), None, duration);
+            }
+        }
     }
 }
 
-fn spawn_tile(commands: &mut Commands, board: &Board, font_spec: &Res<FontSpec>, pos: Position) {
+/// Spawns a tile as a child of the board, so it moves with it.
+fn spawn_tile(
+    commands: &mut Commands,
+    (board_entity, board): (Entity, &Board),
+    font_spec: &Res<FontSpec>,
+    pos: Position,
+    value: u32,
+) -> Entity {
     commands
         .spawn(SpriteBundle {
             sprite: Sprite {
-                color: colours::TILE,
-                custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE)),
+                color: colours::tile(value),
+                custom_size: Some(board.tile_sprite_size()),
                 ..default()
             },
-            transform: Transform::from_xyz(
-                board.cell_position_to_coordinate(pos.x),
-                board.cell_position_to_coordinate(pos.y),
-                2.0,
+            transform: Transform::from_translation(
+                board.cell_position_to_coordinate(pos.x, pos.y).extend(2.0),
             ),
             ..default()
         })
@@ -167,11 +610,11 @@ fn spawn_tile(commands: &mut Commands, board: &Board, font_spec: &Res<FontSpec>,
             child_builder
                 .spawn(Text2dBundle {
                     text: Text::from_section(
-                        "2",
+                        value.to_string(),
                         TextStyle {
                             font: font_spec.family.clone(),
-                            font_size: 40.0,
-                            color: Color::BLACK,
+                            font_size: board.font_size(value),
+                            color: colours::text_on(colours::tile(value)),
                         },
                     )
                     .with_justify(JustifyText::Center),
@@ -180,35 +623,153 @@ fn spawn_tile(commands: &mut Commands, board: &Board, font_spec: &Res<FontSpec>,
                 })
                 .insert(TileText);
         })
-        .insert(Points { value: 2 })
-        .insert(pos);
+        .insert(Points { value })
+        .insert(pos)
+        .set_parent(board_entity)
+        .id()
 }
 
+/// Grows a freshly spawned tile from nothing. It stays hidden for `wait`
+/// first, so it doesn't appear while the other tiles are still sliding.
+fn scale_in(
+    commands: &mut Commands,
+    board: &Board,
+    tile: Entity,
+    (x, y): (u8, u8),
+    wait: Option<Duration>,
+    duration: Duration,
+) {
+    let full = Transform::from_translation(board.cell_position_to_coordinate(x, y).extend(2.0));
+    let hidden = full.with_scale(Vec3::ZERO);
+    let grow = EasingType::Once { duration };
+
+    match wait {
+        Some(wait) => commands.entity(tile).insert((
+            hidden,
+            hidden
+                .ease_to(
+                    hidden,
+                    EaseFunction::QuadraticIn,
+                    EasingType::Once { duration: wait },
+                )
+                .ease_to(full, EaseFunction::BackOut, grow),
+        )),
+        None => commands
+            .entity(tile)
+            .insert((hidden, hidden.ease_to(full, EaseFunction::BackOut, grow))),
+    };
+}
+
+/// A tile that was just merged into, and should pop once it's in place.
+#[derive(Component)]
+struct Merged;
+
+/// A tile that was consumed by a merge, sliding into its partner before it
+/// is despawned.
+#[derive(Component)]
+struct Consumed(Timer);
+
+/// Tiles on the board, along with ones fading out after a merge.
+type AnyTile = Or<(With<Position>, With<Consumed>)>;
+
+/// Tiles that have to slide to a new cell, or pop after a merge.
+type TilesToRender = Or<(Changed<Position>, Added<Merged>)>;
+
+/// Whether a tile is part-way through an easing, or a chain of them.
+type Easing = (
+    Has<EasingComponent<Transform>>,
+    Has<EasingChainComponent<Transform>>,
+);
+
 fn render_tiles(
     mut commands: Commands,
-    mut tiles: Query<(Entity, &Transform, &Position), Changed<Position>>,
+    mut tiles: Query<(Entity, &mut Transform, &Position, Has<Merged>), TilesToRender>,
     query_board: Query<&Board>,
+    settings: Res<Settings>,
 ) {
     let board = query_board.single();
+    let slide = settings.animations.slide();
+    let pop = settings.animations.pop();
 
-    for (entity, transform, pos) in tiles.iter_mut() {
-        let x = board.cell_position_to_coordinate(pos.x);
-        let y = board.cell_position_to_coordinate(pos.y);
-        commands.entity(entity).insert(transform.ease_to(
-            Transform::from_xyz(x, y, transform.translation.z),
-            EaseFunction::QuadraticIn,
-            EasingType::Once {
-                duration: std::time::Duration::from_millis(100),
-            },
-        ));
+    for (entity, mut transform, pos, merged) in tiles.iter_mut() {
+        let coordinate = board.cell_position_to_coordinate(pos.x, pos.y);
+        let target = Transform::from_translation(coordinate.extend(transform.translation.z));
+        let popped = target.with_scale(Vec3::splat(1.2));
+
+        let mut tile = commands.entity(entity);
+        tile.remove::<(
+            Merged,
+            EasingComponent<Transform>,
+            EasingChainComponent<Transform>,
+        )>();
+
+        match (slide, pop.filter(|_| merged)) {
+            (Some(slide), Some(pop)) => tile.insert(
+                transform
+                    .ease_to(
+                        target,
+                        EaseFunction::QuadraticIn,
+                        EasingType::Once { duration: slide },
+                    )
+                    .ease_to(
+                        popped,
+                        EaseFunction::QuadraticOut,
+                        EasingType::Once { duration: pop / 2 },
+                    )
+                    .ease_to(
+                        target,
+                        EaseFunction::QuadraticIn,
+                        EasingType::Once { duration: pop / 2 },
+                    ),
+            ),
+            (None, Some(pop)) => tile.insert(
+                target
+                    .ease_to(
+                        popped,
+                        EaseFunction::QuadraticOut,
+                        EasingType::Once { duration: pop / 2 },
+                    )
+                    .ease_to(
+                        target,
+                        EaseFunction::QuadraticIn,
+                        EasingType::Once { duration: pop / 2 },
+                    ),
+            ),
+            (Some(slide), None) => tile.insert(transform.ease_to(
+                target,
+                EaseFunction::QuadraticIn,
+                EasingType::Once { duration: slide },
+            )),
+            (None, None) => {
+                *transform = target;
+                continue;
+            }
+        };
+    }
+}
+
+fn despawn_consumed(
+    mut commands: Commands,
+    mut tiles: Query<(Entity, &mut Consumed)>,
+    time: Res<Time>,
+) {
+    for (entity, mut consumed) in tiles.iter_mut() {
+        if consumed.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
     }
 }
 
 fn render_tile_points(
     mut texts: Query<&mut Text, With<TileText>>,
-    tiles: Query<(&Points, &Children)>,
+    mut tiles: Query<(&Points, &mut Sprite, &Children), Changed<Points>>,
+    query_board: Query<&Board>,
 ) {
-    for (points, children) in tiles.iter() {
+    let board = query_board.single();
+
+    for (points, mut sprite, children) in tiles.iter_mut() {
+        sprite.color = colours::tile(points.value);
+
         if let Some(entity) = children.first() {
             let mut text = texts.get_mut(*entity).expect("expected Text to exist.");
             let text_section = text
@@ -216,230 +777,382 @@ fn render_tile_points(
                 .first_mut()
                 .expect("expect first section to be accessible as a mutable");
             text_section.value = points.value.to_string();
+            text_section.style.font_size = board.font_size(points.value);
+            text_section.style.color = colours::text_on(sprite.color);
         }
     }
 }
 
-enum BoardShift {
-    Left,
-    Right,
-    Up,
-    Down,
-}
+/// Asks for the board to be shifted, from the move queue or anything else
+/// that drives the game. Only the first request each frame is acted on.
+#[derive(Event)]
+struct ShiftRequestEvent(BoardShift);
+
+/// A shift changed the board.
+#[derive(Event)]
+struct BoardShiftedEvent(BoardShift);
 
-impl TryFrom<&KeyCode> for BoardShift {
-    type Error = &'static str;
+/// Moves the player has made that haven't been played yet, oldest first.
+#[derive(Resource, Default, Deref, DerefMut)]
+struct MoveQueue(VecDeque<BoardShift>);
 
-    fn try_from(value: &KeyCode) -> Result<Self, Self::Error> {
-        match value {
-            KeyCode::ArrowLeft => Ok(BoardShift::Left),
-            KeyCode::ArrowUp => Ok(BoardShift::Up),
-            KeyCode::ArrowRight => Ok(BoardShift::Right),
-            KeyCode::ArrowDown => Ok(BoardShift::Down),
-            _ => Err("not a valid board_shift key"),
+fn queue_moves(
+    mut action_reader: EventReader<ActionEvent>,
+    mut queue: ResMut<MoveQueue>,
+    settings: Res<Settings>,
+) {
+    for shift in action_reader
+        .read()
+        .filter_map(|ActionEvent(action)| action.shift())
+    {
+        if queue.len() < settings.move_buffer.max(1) {
+            queue.push_back(shift);
         }
     }
 }
 
-impl BoardShift {
-    fn sort(&self, a: &Position, b: &Position) -> Ordering {
-        match self {
-            BoardShift::Left => match Ord::cmp(&a.y, &b.y) {
-                Ordering::Equal => Ord::cmp(&a.x, &b.x),
-                ordering => ordering,
-            },
-            BoardShift::Right => match Ord::cmp(&b.y, &a.y) {
-                Ordering::Equal => Ord::cmp(&b.x, &a.x),
-                ordering => ordering,
-            },
-            BoardShift::Up => match Ord::cmp(&b.x, &a.x) {
-                Ordering::Equal => Ord::cmp(&b.y, &a.y),
-                ordering => ordering,
-            },
-            BoardShift::Down => match Ord::cmp(&a.x, &b.x) {
-                Ordering::Equal => Ord::cmp(&a.y, &b.y),
-                ordering => ordering,
-            },
-        }
+/// Plays the next queued move once every tile has finished animating, or
+/// straight away when `fast_forward` is on.
+fn dispatch_moves(
+    mut commands: Commands,
+    mut queue: ResMut<MoveQueue>,
+    mut tiles: Query<(Entity, &Position, &mut Transform, Easing)>,
+    consumed: Query<Entity, With<Consumed>>,
+    query_board: Query<&Board>,
+    settings: Res<Settings>,
+    mut shift_writer: EventWriter<ShiftRequestEvent>,
+) {
+    if queue.is_empty() {
+        return;
     }
 
-    fn set_col(&self, board_size: u8, pos: &mut Mut<Position>, idx: u8) {
-        match self {
-            BoardShift::Left => {
-                pos.x = idx;
-            }
-            BoardShift::Right => {
-                pos.x = board_size - 1 - idx;
-            }
-            BoardShift::Up => {
-                pos.y = board_size - 1 - idx;
-            }
-            BoardShift::Down => {
-                pos.y = idx;
-            }
+    let animating = !consumed.is_empty()
+        || tiles
+            .iter()
+            .any(|(_, _, _, (easing, chain))| easing || chain);
+    if animating {
+        if !settings.fast_forward {
+            return;
         }
-    }
 
-    fn get_row(&self, pos: &Position) -> u8 {
-        match self {
-            BoardShift::Left | BoardShift::Right => pos.y,
-            BoardShift::Up | BoardShift::Down => pos.x,
+        // Jump every tile to where its animation would have left it
+        let board = query_board.single();
+        for (entity, pos, mut transform, _) in tiles.iter_mut() {
+            commands.entity(entity).remove::<(
+                Merged,
+                EasingComponent<Transform>,
+                EasingChainComponent<Transform>,
+            )>();
+            *transform = Transform::from_translation(
+                board.cell_position_to_coordinate(pos.x, pos.y).extend(2.0),
+            );
+        }
+        for entity in consumed.iter() {
+            commands.entity(entity).despawn_recursive();
         }
     }
+
+    if let Some(shift) = queue.pop_front() {
+        shift_writer.send(ShiftRequestEvent(shift));
+    }
+}
+
+/// Forgets queued moves that were meant for a board that's gone.
+fn clear_moves(mut queue: ResMut<MoveQueue>) {
+    queue.clear();
 }
 
+#[allow(clippy::too_many_arguments)]
 fn board_shift(
     mut commands: Commands,
-    input: Res<ButtonInput<KeyCode>>,
-    mut tiles: Query<(Entity, &mut Position, &mut Points)>,
+    mut shift_reader: EventReader<ShiftRequestEvent>,
+    mut tiles: Query<(Entity, &mut Position, &mut Points, &Transform)>,
     query_board: Query<&Board>,
     mut tile_writer: EventWriter<NewTileEvent>,
+    mut no_op_writer: EventWriter<NoOpMoveEvent>,
+    mut shifted_writer: EventWriter<BoardShiftedEvent>,
+    mut score_writer: EventWriter<ScoreChanged>,
     mut game: ResMut<Game>,
+    mut history: ResMut<History>,
+    rng: Res<GameRng>,
+    settings: Res<Settings>,
 ) {
-    let shift_direction = input
-        .get_just_pressed()
-        .find_map(|key_code| BoardShift::try_from(key_code).ok());
+    let shift_direction = shift_reader.read().next().map(|request| request.0);
+    shift_reader.clear();
 
     if let Some(shift) = shift_direction {
-        tile_writer.send(NewTileEvent);
-        if game.score_best < game.score {
-            game.score_best = game.score;
+        let mut board_tiles: HashMap<(u8, u8), Entity> = tiles
+            .iter()
+            .map(|(entity, pos, _, _)| ((pos.x, pos.y), entity))
+            .collect();
+
+        let before = Snapshot::capture(&game, &rng);
+        let outcome = game.grid.apply_move(shift);
+
+        if !outcome.changed() {
+            // Nothing slid or merged, so the move doesn't count
+            no_op_writer.send(NoOpMoveEvent(shift));
+            return;
         }
 
-        let mut it = tiles
-            .iter_mut()
-            .sorted_by(|a, b| shift.sort(&a.1, &b.1))
-            .peekable();
-        let mut column: u8 = 0;
+        history.record(before, settings.undo_limit);
+        shifted_writer.send(BoardShiftedEvent(shift));
+        tile_writer.send(NewTileEvent);
+        game.moves += 1;
 
-        let board = query_board.single();
+        for tile_move in outcome.moves.iter() {
+            let entity = board_tiles
+                .remove(&tile_move.from)
+                .expect("every tile in the grid should have an entity");
 
-        while let Some(mut tile) = it.next() {
-            shift.set_col(board.size, &mut tile.1, column);
-            if let Some(tile_next) = it.peek() {
-                if shift.get_row(&tile.1) != shift.get_row(&tile_next.1) {
-                    // Not in the same row, no merge
-                    column = 0;
-                } else if tile.2.value != tile_next.2.value {
-                    // Not the same value, no merge
-                    column = column + 1;
-                } else {
-                    // Merge
-                    // Remove tile from it so we don't evaluate it again
-                    let real_next_tile = it
-                        .next()
-                        .expect("A peeked tile should always exist when next() is called");
-                    tile.2.value = tile.2.value + real_next_tile.2.value;
-
-                    // Add to total score
-                    game.score += tile.2.value;
-
-                    // Despawn the tile from the board
-                    commands.entity(real_next_tile.0).despawn_recursive();
-
-                    if let Some(future) = it.peek() {
-                        if shift.get_row(&tile.1) != shift.get_row(&future.1) {
-                            // Next tile starts next row
-                            column = 0;
-                        } else {
-                            // Next tile in same row, increment column
-                            column = column + 1;
-                        }
+            if tile_move.merged {
+                // Take the tile off the board, sliding it under its partner
+                // before it goes
+                match settings.animations.slide() {
+                    Some(duration) => {
+                        let (_, _, _, transform) =
+                            tiles.get(entity).expect("tile entity should exist");
+                        let board = query_board.single();
+                        let (x, y) = tile_move.to;
+                        let target = Transform::from_translation(
+                            board.cell_position_to_coordinate(x, y).extend(1.5),
+                        );
+                        commands
+                            .entity(entity)
+                            .remove::<(Position, Points)>()
+                            .insert((
+                                transform.ease_to(
+                                    target,
+                                    EaseFunction::QuadraticIn,
+                                    EasingType::Once { duration },
+                                ),
+                                Consumed(Timer::new(duration, TimerMode::Once)),
+                            ));
                     }
+                    None => commands.entity(entity).despawn_recursive(),
                 }
+            } else {
+                let (_, mut pos, _, _) = tiles.get_mut(entity).expect("tile entity should exist");
+                pos.x = tile_move.to.0;
+                pos.y = tile_move.to.1;
+                board_tiles.insert(tile_move.to, entity);
             }
         }
+
+        for merge in outcome.merges.iter() {
+            let entity = board_tiles[&merge.position];
+            let (_, _, mut points, _) = tiles.get_mut(entity).expect("merged tile should exist");
+            points.value = merge.value;
+            commands.entity(entity).insert(Merged);
+            score_writer.send(ScoreChanged(merge.clone()));
+        }
+    }
+}
+
+/// Points were scored by a merge: the value of the merged tile, and where
+/// it is.
+#[derive(Event)]
+struct ScoreChanged(Merge);
+
+/// Adds merged points to the score. Only a game that's being played can
+/// set a new best, not a replay of one.
+fn update_score(
+    mut score_reader: EventReader<ScoreChanged>,
+    mut game: ResMut<Game>,
+    run_state: Res<State<RunState>>,
+) {
+    for ScoreChanged(merge) in score_reader.read() {
+        match run_state.get() {
+            RunState::Playing => game.add_score(merge.value),
+            _ => game.score += merge.value,
+        }
     }
 }
 
 #[derive(Event)]
 struct NewTileEvent;
 
+/// A move was attempted that would leave the board unchanged.
+#[derive(Event)]
+struct NoOpMoveEvent(BoardShift);
+
+fn shake_board(
+    mut commands: Commands,
+    mut no_op_reader: EventReader<NoOpMoveEvent>,
+    query_board: Query<Entity, With<Board>>,
+    settings: Res<Settings>,
+) {
+    if !settings.animations.enabled {
+        no_op_reader.clear();
+        return;
+    }
+
+    if let Some(NoOpMoveEvent(shift)) = no_op_reader.read().last() {
+        let (x, y) = match shift {
+            BoardShift::Left => (-1.0, 0.0),
+            BoardShift::Right => (1.0, 0.0),
+            BoardShift::Up => (0.0, 1.0),
+            BoardShift::Down => (0.0, -1.0),
+        };
+        let nudge = Transform::from_xyz(x * TILE_SPACER, y * TILE_SPACER, 0.0);
+
+        commands.entity(query_board.single()).insert(
+            Transform::IDENTITY
+                .ease_to(
+                    nudge,
+                    EaseFunction::QuadraticOut,
+                    EasingType::Once {
+                        duration: Duration::from_millis(50),
+                    },
+                )
+                .ease_to(
+                    Transform::IDENTITY,
+                    EaseFunction::QuadraticIn,
+                    EasingType::Once {
+                        duration: Duration::from_millis(50),
+                    },
+                ),
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn new_tile_handler(
     mut tile_reader: EventReader<NewTileEvent>,
     mut commands: Commands,
-    query_board: Query<&Board>,
-    tiles: Query<&Position>,
+    query_board: Query<(Entity, &Board)>,
+    mut game: ResMut<Game>,
+    mut rng: ResMut<GameRng>,
+    spawns: Res<TileSpawns>,
     font_spec: Res<FontSpec>,
+    settings: Res<Settings>,
 ) {
-    let board = query_board.single();
+    let (board_entity, board) = query_board.single();
 
     for _event in tile_reader.read() {
-        let mut rng = rand::thread_rng();
-        let possible_position: Option<Position> = (0..board.size)
-            .cartesian_product(0..board.size)
-            .filter_map(|tile_pos| {
-                let new_pos = Position {
-                    x: tile_pos.0,
-                    y: tile_pos.1,
-                };
-
-                match tiles.iter().find(|pos| **pos == new_pos) {
-                    None => Some(new_pos),
-                    Some(_) => None,
-                }
-            })
-            .choose(&mut rng);
-
-        if let Some(pos) = possible_position {
-            spawn_tile(&mut commands, board, &font_spec, pos)
+        if let Some(((x, y), value)) = game.grid.spawn_random(&mut rng.0, &spawns) {
+            let tile = spawn_tile(
+                &mut commands,
+                (board_entity, board),
+                &font_spec,
+                Position { x, y },
+                value,
+            );
+            if let Some(duration) = settings.animations.spawn() {
+                let wait = settings.animations.slide();
+                scale_in(&mut commands, board, tile, (x, y), wait, duration);
+            }
         }
     }
 }
 
-fn end_game(
-    tiles: Query<(&Position, &Points)>,
-    query_board: Query<&Board>,
+fn end_game(game: Res<Game>, mut run_state: ResMut<NextState<RunState>>) {
+    if game.grid.is_game_over() {
+        run_state.set(RunState::GameOver);
+    }
+}
+
+fn win_game(
+    mut game: ResMut<Game>,
+    settings: Res<Settings>,
     mut run_state: ResMut<NextState<RunState>>,
 ) {
-    let board = query_board.single();
-
-    if tiles.iter().len() == 16 {
-        let map: HashMap<&Position, &Points> = tiles.iter().collect();
-        let neighbor_points = [(-1, 0), (0, 1), (1, 0), (0, -1)];
-        let board_range: Range<i8> = 0..(board.size as i8);
-
-        let has_move = tiles.iter().any(|(Position { x, y }, value)| {
-            neighbor_points
-                .iter()
-                .filter_map(|(x2, y2)| {
-                    let new_x = *x as i8 - x2;
-                    let new_y = *y as i8 - y2;
-
-                    if !board_range.contains(&new_x) || !board_range.contains(&new_y) {
-                        return None;
-                    }
-
-                    map.get(&Position {
-                        x: new_x.try_into().unwrap(),
-                        y: new_y.try_into().unwrap(),
-                    })
-                })
-                .any(|&v| v == value)
-        });
-
-        if has_move == false {
-            dbg!("game over!");
-            run_state.set(RunState::GameOver);
-        }
+    if !game.won && game.grid.max_tile() >= Some(settings.win_target) {
+        game.won = true;
+        run_state.set(RunState::Won);
     }
 }
 
-#[derive(Default, Debug, Clone, Eq, PartialEq, Hash, States)]
+#[derive(Default, Debug, Clone, Eq, PartialEq, Hash, States, Serialize, Deserialize)]
 enum RunState {
     #[default]
     Playing,
     GameOver,
+    Won,
+    /// Watching a recorded game.
+    Replay,
+    Paused,
 }
 
+/// Throws away the current board and starts over.
+#[derive(Event)]
+struct NewGameEvent;
+
+/// `Game::grid` was replaced as a whole, so the tiles on screen are stale.
+#[derive(Event)]
+struct GridRestoredEvent;
+
+/// Picks up the game saved when the app last closed, or starts a new one.
+#[allow(clippy::too_many_arguments)]
+fn restore_game(
+    mut game: ResMut<Game>,
+    mut rng: ResMut<GameRng>,
+    mut recorder: ResMut<Recorder>,
+    mut settings: ResMut<Settings>,
+    profile: Res<Profile>,
+    mut run_state: ResMut<NextState<RunState>>,
+    cli: Res<Cli>,
+    mut new_game: EventWriter<NewGameEvent>,
+    mut restored: EventWriter<GridRestoredEvent>,
+) {
+    // A replay sets up its own game
+    if cli.replay.is_some() {
+        return;
+    }
+
+    let Some(saved) = persistence::game_to_resume(cli.seed) else {
+        new_game.send(NewGameEvent);
+        return;
+    };
+
+    let state;
+    (*game, *rng, state) = Game::from_saved(saved, &mut settings, &profile, &mut recorder);
+    run_state.set(state);
+    restored.send(GridRestoredEvent);
+}
+
+fn resync_tiles(
+    mut commands: Commands,
+    tiles: Query<Entity, AnyTile>,
+    query_board: Query<(Entity, &Board)>,
+    game: Res<Game>,
+    font_spec: Res<FontSpec>,
+) {
+    let (board_entity, board) = query_board.single();
+
+    for entity in tiles.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    for ((x, y), value) in game.grid.tiles() {
+        spawn_tile(
+            &mut commands,
+            (board_entity, board),
+            &font_spec,
+            Position { x, y },
+            value,
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn game_reset(
     mut commands: Commands,
-    tiles: Query<Entity, With<Position>>,
+    tiles: Query<Entity, AnyTile>,
+    query_board: Query<&Board>,
     mut game: ResMut<Game>,
+    mut rng: ResMut<GameRng>,
+    profile: Res<Profile>,
+    settings: Res<Settings>,
+    cli: Res<Cli>,
+    mut run_state: ResMut<NextState<RunState>>,
 ) {
+    let board = query_board.single();
+
     for entity in tiles.iter() {
         commands.entity(entity).despawn_recursive();
     }
 
-    game.score = 0;
+    (*game, *rng) = Game::new_seeded((board.width, board.height), cli.seed, &settings, &profile);
+    run_state.set(RunState::Playing);
 }
@@ -0,0 +1,39 @@
+//! Pausing, with the Pause key or when the window loses focus.
+//!
+//! Virtual time stops while paused, so animations, timers and the replay
+//! and autoplay clocks all hold still until play carries on.
+
+use crate::{RunState, Settings};
+use bevy::{prelude::*, window::WindowFocused};
+
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(RunState::Paused), stop_time)
+            .add_systems(OnExit(RunState::Paused), start_time)
+            .add_systems(
+                Update,
+                pause_on_focus_loss.run_if(in_state(RunState::Playing)),
+            );
+    }
+}
+
+fn stop_time(mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+}
+
+fn start_time(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
+
+fn pause_on_focus_loss(
+    mut focus_reader: EventReader<WindowFocused>,
+    settings: Res<Settings>,
+    mut next_state: ResMut<NextState<RunState>>,
+) {
+    let lost = focus_reader.read().any(|event| !event.focused);
+    if lost && settings.pause_on_focus_loss {
+        next_state.set(RunState::Paused);
+    }
+}
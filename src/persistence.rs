@@ -0,0 +1,394 @@
+//! Player data kept between sessions, stored as RON files in the platform
+//! config directory (`$XDG_CONFIG_HOME/two-oh-four-eight` on Linux).
+//!
+//! Every file carries a `version` so future builds can tell which shape
+//! they're reading. Missing files fall back to defaults silently, broken
+//! ones with a logged warning.
+
+use crate::{
+    controls::KeyBindings, recorder::Recorder, update_score, Game, GameRng, RunState, Settings,
+    BOARD_SIZE_RANGE,
+};
+use bevy::{app::AppExit, prelude::*, time::common_conditions::on_timer};
+use directories::ProjectDirs;
+use rand_chacha::ChaCha8Rng;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use two_oh_four_eight::{engine::Grid, replay::Replay};
+
+/// Bumped whenever a saved file changes shape in a way older builds can't read.
+pub const FORMAT_VERSION: u32 = 1;
+
+const PROFILE_FILE: &str = "profile.ron";
+const SAVE_FILE: &str = "game.ron";
+
+/// How often the game in progress is saved, on top of saving on exit.
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+pub struct PersistencePlugin;
+
+impl Plugin for PersistencePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreStartup, load_profile)
+            .add_systems(
+                Update,
+                (
+                    // In the same frame as the score, so the move that ends
+                    // a game still counts
+                    record_best
+                        .after(update_score)
+                        .run_if(not(in_state(RunState::Replay))),
+                    sync_settings.run_if(resource_changed::<Settings>),
+                    sync_keys.run_if(resource_changed::<KeyBindings>),
+                    save_profile.run_if(resource_changed::<Profile>),
+                    save_game.run_if(
+                        on_timer(AUTOSAVE_INTERVAL).and_then(not(in_state(RunState::Replay))),
+                    ),
+                )
+                    .chain(),
+            )
+            // Watching a replay leaves the saved game alone
+            .add_systems(
+                Last,
+                save_game.run_if(on_event::<AppExit>().and_then(not(in_state(RunState::Replay)))),
+            );
+    }
+}
+
+/// Best scores and settings, everything that outlives a single game.
+#[derive(Resource, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub version: u32,
+    /// The best score on any board size.
+    pub best: u32,
+    /// Best scores keyed by board size, e.g. `"4x4"`.
+    pub bests: BTreeMap<String, u32>,
+    pub settings: Settings,
+    pub keys: KeyBindings,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile {
+            version: FORMAT_VERSION,
+            best: 0,
+            bests: BTreeMap::new(),
+            settings: Settings::default(),
+            keys: KeyBindings::default(),
+        }
+    }
+}
+
+impl Profile {
+    pub fn best_for(&self, (width, height): (u8, u8)) -> u32 {
+        self.bests
+            .get(&format!("{width}x{height}"))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Keeps `score` if it beats the best for its board size.
+    pub fn record(&mut self, (width, height): (u8, u8), score: u32) {
+        let best = self.bests.entry(format!("{width}x{height}")).or_default();
+        *best = score.max(*best);
+        self.best = score.max(self.best);
+    }
+}
+
+/// Everything needed to carry on with a game exactly where it was left.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedGame {
+    pub version: u32,
+    pub grid: Grid,
+    pub score: u32,
+    pub moves: u32,
+    #[serde(default)]
+    pub seed: u64,
+    pub won: bool,
+    #[serde(default)]
+    pub hints_used: u32,
+    #[serde(default)]
+    pub new_best: bool,
+    pub rng: ChaCha8Rng,
+    pub run_state: RunState,
+    /// The recording of the game so far.
+    #[serde(default)]
+    pub replay: Option<Replay>,
+}
+
+#[derive(Debug)]
+pub enum PersistenceError {
+    NoConfigDir,
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for PersistenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PersistenceError::NoConfigDir => write!(f, "no config directory for this platform"),
+            PersistenceError::Io(err) => write!(f, "{err}"),
+            PersistenceError::Parse(err) => write!(f, "corrupt file: {err}"),
+            PersistenceError::Serialize(err) => write!(f, "{err}"),
+            PersistenceError::UnsupportedVersion(version) => write!(
+                f,
+                "format version {version} is newer than this build understands ({FORMAT_VERSION})"
+            ),
+        }
+    }
+}
+
+impl From<io::Error> for PersistenceError {
+    fn from(err: io::Error) -> Self {
+        PersistenceError::Io(err)
+    }
+}
+
+impl From<ron::error::SpannedError> for PersistenceError {
+    fn from(err: ron::error::SpannedError) -> Self {
+        PersistenceError::Parse(err)
+    }
+}
+
+impl From<ron::Error> for PersistenceError {
+    fn from(err: ron::Error) -> Self {
+        PersistenceError::Serialize(err)
+    }
+}
+
+pub fn config_dir() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "two-oh-four-eight").map(|dirs| dirs.config_dir().to_path_buf())
+}
+
+/// Reads `file_name` from the config directory, `None` if it doesn't exist.
+pub fn read<T: DeserializeOwned>(file_name: &str) -> Result<Option<T>, PersistenceError> {
+    let path = config_dir()
+        .ok_or(PersistenceError::NoConfigDir)?
+        .join(file_name);
+
+    match read_path(&path) {
+        Ok(value) => Ok(Some(value)),
+        Err(PersistenceError::Io(err)) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Reads a RON file from anywhere, such as a replay passed on the command line.
+pub fn read_path<T: DeserializeOwned>(path: &Path) -> Result<T, PersistenceError> {
+    let contents = fs::read_to_string(path)?;
+    Ok(ron::from_str(&contents)?)
+}
+
+/// Writes `value` to `file_name` in the config directory, which may include
+/// subdirectories. The file is replaced in one go, so a crash mid-write
+/// can't leave half a file behind.
+pub fn write<T: Serialize>(file_name: &str, value: &T) -> Result<(), PersistenceError> {
+    let path = config_dir()
+        .ok_or(PersistenceError::NoConfigDir)?
+        .join(file_name);
+
+    write_path(&path, value)
+}
+
+/// Writes a RON file anywhere, creating any missing parent directories.
+pub fn write_path<T: Serialize>(path: &Path, value: &T) -> Result<(), PersistenceError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let contents = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())?;
+    let tmp_path = path.with_extension("ron.tmp");
+    fs::write(&tmp_path, contents)?;
+    fs::rename(tmp_path, path)?;
+
+    Ok(())
+}
+
+/// Loads the profile, starting from scratch if it is missing or unreadable.
+pub fn load_profile_or_default() -> Profile {
+    match read::<Profile>(PROFILE_FILE) {
+        Ok(Some(profile)) if profile.version > FORMAT_VERSION => {
+            let err = PersistenceError::UnsupportedVersion(profile.version);
+            warn!("ignoring {PROFILE_FILE}: {err}");
+            Profile::default()
+        }
+        Ok(Some(mut profile)) => {
+            profile.version = FORMAT_VERSION;
+            if let Some((binding, first, second)) = profile.keys.conflicts().first() {
+                warn!(
+                    "resetting key bindings from {PROFILE_FILE}: {binding} is bound to both {} and {}",
+                    first.label(),
+                    second.label()
+                );
+                profile.keys = KeyBindings::default();
+            }
+            profile.keys.fill_missing();
+            profile
+        }
+        Ok(None) => Profile::default(),
+        Err(err) => {
+            warn!("ignoring {PROFILE_FILE}: {err}");
+            Profile::default()
+        }
+    }
+}
+
+/// Loads the game saved on the last exit, if there is a usable one.
+pub fn load_saved_game() -> Option<SavedGame> {
+    match read::<SavedGame>(SAVE_FILE) {
+        Ok(Some(saved)) if saved.version > FORMAT_VERSION => {
+            let err = PersistenceError::UnsupportedVersion(saved.version);
+            warn!("ignoring {SAVE_FILE}: {err}");
+            None
+        }
+        Ok(Some(saved))
+            if !BOARD_SIZE_RANGE.contains(&saved.grid.width())
+                || !BOARD_SIZE_RANGE.contains(&saved.grid.height()) =>
+        {
+            warn!(
+                "ignoring {SAVE_FILE}: unsupported board size {}x{}",
+                saved.grid.width(),
+                saved.grid.height()
+            );
+            None
+        }
+        Ok(saved) => saved,
+        Err(err) => {
+            warn!("ignoring {SAVE_FILE}: {err}");
+            None
+        }
+    }
+}
+
+/// The saved game to carry on with, unless `--seed` asks for a fresh one.
+pub fn game_to_resume(cli_seed: Option<u64>) -> Option<SavedGame> {
+    // Asking for a seed means asking for a fresh game with it
+    match cli_seed {
+        Some(_) => None,
+        None => load_saved_game(),
+    }
+}
+
+fn load_profile(mut commands: Commands) {
+    let profile = load_profile_or_default();
+    commands.insert_resource(profile.settings.clone());
+    commands.insert_resource(profile.keys.clone());
+    commands.insert_resource(profile);
+}
+
+fn record_best(game: Res<Game>, mut profile: ResMut<Profile>) {
+    let size = (game.grid.width(), game.grid.height());
+    if profile.best_for(size) < game.score_best {
+        profile.record(size, game.score_best);
+    }
+}
+
+fn sync_settings(settings: Res<Settings>, mut profile: ResMut<Profile>) {
+    profile.settings = settings.clone();
+}
+
+fn sync_keys(keys: Res<KeyBindings>, mut profile: ResMut<Profile>) {
+    profile.keys = keys.clone();
+}
+
+/// Writes the profile, logging rather than returning any failure.
+pub fn write_profile(profile: &Profile) {
+    if let Err(err) = write(PROFILE_FILE, profile) {
+        warn!("could not save {PROFILE_FILE}: {err}");
+    }
+}
+
+/// Writes the game in progress, logging rather than returning any failure.
+pub fn write_game(game: &Game, rng: &GameRng, run_state: &RunState, recorder: &Recorder) {
+    let saved = SavedGame {
+        version: FORMAT_VERSION,
+        grid: game.grid.clone(),
+        score: game.score,
+        moves: game.moves,
+        seed: game.seed,
+        won: game.won,
+        hints_used: game.hints_used,
+        new_best: game.new_best,
+        rng: rng.0.clone(),
+        run_state: run_state.clone(),
+        replay: recorder.replay().cloned(),
+    };
+
+    if let Err(err) = write(SAVE_FILE, &saved) {
+        warn!("could not save {SAVE_FILE}: {err}");
+    }
+}
+
+fn save_profile(profile: Res<Profile>) {
+    write_profile(&profile);
+}
+
+fn save_game(
+    game: Res<Game>,
+    rng: Res<GameRng>,
+    run_state: Res<State<RunState>>,
+    recorder: Res<Recorder>,
+) {
+    write_game(&game, &rng, run_state.get(), &recorder);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn saved_game_round_trips() {
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        // Move the RNG on, so there is a stream position to save
+        let _: u64 = rng.gen();
+        let mut grid = Grid::new(4, 4);
+        grid.set((0, 0), Some(2));
+        grid.set((3, 2), Some(128));
+
+        let saved = SavedGame {
+            version: FORMAT_VERSION,
+            grid,
+            score: 1234,
+            moves: 56,
+            seed: 42,
+            won: true,
+            hints_used: 2,
+            new_best: true,
+            rng,
+            run_state: RunState::GameOver,
+            replay: None,
+        };
+
+        let contents = ron::ser::to_string_pretty(&saved, ron::ser::PrettyConfig::default())
+            .expect("the saved game should serialise");
+        let parsed: SavedGame = ron::from_str(&contents).expect("the saved game should parse");
+        assert_eq!(parsed, saved);
+    }
+
+    #[test]
+    fn writes_into_subdirectories() {
+        let dir = std::env::temp_dir().join(format!("two-oh-four-eight-{}", std::process::id()));
+        let path = dir.join("replays").join("game.ron");
+        let bests = BTreeMap::from([("4x4".to_string(), 2048)]);
+
+        write_path(&path, &bests).expect("the file should be written");
+        let read: BTreeMap<String, u32> = read_path(&path).expect("the file should read back");
+        let leftovers: Vec<_> = fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(read, bests);
+        assert_eq!(leftovers, ["game.ron"]);
+    }
+}
@@ -0,0 +1,141 @@
+//! "+N" popups for the points scored by a move.
+//!
+//! Each merge gets one over the merged tile, and the move's total gets one
+//! beside the score box. Both float up and fade out over
+//! `animations.popup_ms`.
+
+use crate::{ui::ScoreDisplay, update_score, Board, FontSpec, ScoreChanged, Settings};
+use bevy::prelude::*;
+use bevy_easings::*;
+
+pub struct ScorePopupPlugin;
+
+impl Plugin for ScorePopupPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            ((tile_popups, score_popup).after(update_score), fade_popups),
+        );
+    }
+}
+
+/// Fades out and despawns when the timer runs out.
+#[derive(Component)]
+struct Popup(Timer);
+
+fn tile_popups(
+    mut commands: Commands,
+    mut score_reader: EventReader<ScoreChanged>,
+    query_board: Query<(Entity, &Board)>,
+    settings: Res<Settings>,
+    font_spec: Res<FontSpec>,
+) {
+    let Some(duration) = settings.animations.popup() else {
+        score_reader.clear();
+        return;
+    };
+    let (board_entity, board) = query_board.single();
+
+    for ScoreChanged(merge) in score_reader.read() {
+        let (x, y) = merge.position;
+        let start =
+            Transform::from_translation(board.cell_position_to_coordinate(x, y).extend(5.0));
+        let end = start.with_translation(start.translation + Vec3::Y * board.tile_size);
+
+        let popup = commands
+            .spawn((
+                Text2dBundle {
+                    text: Text::from_section(
+                        format!("+{}", merge.value),
+                        TextStyle {
+                            font: font_spec.family.clone(),
+                            font_size: board.tile_size * 0.45,
+                            color: Color::WHITE,
+                        },
+                    ),
+                    transform: start,
+                    ..default()
+                },
+                start.ease_to(
+                    end,
+                    EaseFunction::QuadraticOut,
+                    EasingType::Once { duration },
+                ),
+                Popup(Timer::new(duration, TimerMode::Once)),
+            ))
+            .id();
+        commands.entity(board_entity).add_child(popup);
+    }
+}
+
+/// One popup for the whole move, however many merges it made.
+fn score_popup(
+    mut commands: Commands,
+    mut score_reader: EventReader<ScoreChanged>,
+    query_score: Query<&Parent, With<ScoreDisplay>>,
+    settings: Res<Settings>,
+    font_spec: Res<FontSpec>,
+) {
+    let points: u32 = score_reader
+        .read()
+        .map(|ScoreChanged(merge)| merge.value)
+        .sum();
+    let Some(duration) = settings.animations.popup() else {
+        return;
+    };
+    if points == 0 {
+        return;
+    }
+
+    // Just above the score box, rising away from it
+    let start = Style {
+        position_type: PositionType::Absolute,
+        width: Val::Percent(100.0),
+        bottom: Val::Percent(100.0),
+        ..default()
+    };
+    let end = Style {
+        bottom: Val::Percent(180.0),
+        ..start.clone()
+    };
+
+    let popup = commands
+        .spawn((
+            TextBundle::from_section(
+                format!("+{points}"),
+                TextStyle {
+                    font: font_spec.family.clone(),
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_text_justify(JustifyText::Center)
+            .with_style(start.clone()),
+            start.ease_to(
+                end,
+                EaseFunction::QuadraticOut,
+                EasingType::Once { duration },
+            ),
+            Popup(Timer::new(duration, TimerMode::Once)),
+        ))
+        .id();
+    commands.entity(query_score.single().get()).add_child(popup);
+}
+
+fn fade_popups(
+    mut commands: Commands,
+    mut popups: Query<(Entity, &mut Popup, &mut Text)>,
+    time: Res<Time>,
+) {
+    for (entity, mut popup, mut text) in popups.iter_mut() {
+        if popup.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        let alpha = 1.0 - popup.0.fraction();
+        for section in text.sections.iter_mut() {
+            section.style.color.set_a(alpha);
+        }
+    }
+}
@@ -0,0 +1,386 @@
+//! Records every game as a [`Replay`] and plays replays back on the board.
+//!
+//! Finished games are written to `replays/` in the config directory. Passing
+//! `--replay <file>` watches one instead of playing: Space pauses, the left
+//! and right arrows step through moves and up and down change the speed.
+
+use crate::{
+    board_shift, history::apply_history, persistence, restore_game, resync_tiles, spawn_board,
+    spawn_tiles, ui::despawn_with, BoardShiftedEvent, FontSpec, Game, GameRng, GridRestoredEvent,
+    NewGameEvent, RunState, ShiftRequestEvent, TileSpawns, BOARD_SIZE_RANGE,
+};
+use bevy::prelude::*;
+use std::{
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use two_oh_four_eight::{
+    engine::BoardShift,
+    replay::{Replay, ReplayMove, ReplayPosition, REPLAY_VERSION},
+};
+
+const SPEEDS: [f32; 7] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+
+/// Records games, and plays back `replay` instead when there is one.
+pub struct ReplayPlugin {
+    pub replay: Option<Replay>,
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        if let Some(replay) = &self.replay {
+            app.insert_resource(ReplayPlayer::new(replay.clone()));
+        }
+
+        app.init_resource::<Recorder>()
+            .add_systems(
+                Startup,
+                start_playback.after(restore_game).before(spawn_board),
+            )
+            .add_systems(
+                Update,
+                (
+                    start_recording
+                        .after(spawn_tiles)
+                        .run_if(on_event::<NewGameEvent>()),
+                    (tick_recording, record_move.after(board_shift))
+                        .run_if(in_state(RunState::Playing)),
+                    // A move and an undo in the same frame are kept in order
+                    follow_history.after(apply_history).after(record_move),
+                    // Before the tiles are resynced and the board shifts, so a
+                    // step back never lands between a move and its shift
+                    (replay_controls, replay_autoplay, replay_hud)
+                        .chain()
+                        .before(resync_tiles)
+                        .run_if(in_state(RunState::Replay)),
+                ),
+            )
+            .add_systems(OnEnter(RunState::GameOver), save_recording)
+            .add_systems(OnEnter(RunState::Replay), spawn_replay_hud)
+            .add_systems(OnExit(RunState::Replay), despawn_with::<ReplayHud>);
+    }
+}
+
+/// The recording of the game being played.
+#[derive(Resource, Default)]
+pub struct Recorder {
+    replay: Option<Replay>,
+    /// Where the recording will be saved, relative to the config directory.
+    file_name: String,
+    /// Time spent playing this game, used to stamp moves.
+    clock: Duration,
+    /// Moves taken back with undo, most recent last, so redo can restore them.
+    undone: Vec<ReplayMove>,
+    /// The recording is already on disk as it stands.
+    saved: bool,
+}
+
+impl Recorder {
+    pub fn replay(&self) -> Option<&Replay> {
+        self.replay.as_ref()
+    }
+
+    /// Starts recording into `replay`, which may already hold moves when
+    /// carrying on with a saved game.
+    pub fn start(&mut self, replay: Replay) {
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        self.file_name = format!("replays/{started}-{}.ron", replay.seed);
+        self.clock = replay
+            .moves
+            .last()
+            .map(|last| Duration::from_millis(last.at_ms))
+            .unwrap_or_default();
+        self.undone.clear();
+        self.saved = false;
+        self.replay = Some(replay);
+    }
+
+    /// Notes that the recording was already written, such as a finished
+    /// game restored from the last session, so it isn't saved twice.
+    pub fn mark_saved(&mut self) {
+        self.saved = true;
+    }
+
+    /// Time spent playing this game so far.
+    pub fn play_time(&self) -> Duration {
+        self.clock
+    }
+
+    /// Moves the game clock on, while the game is being played.
+    pub fn tick(&mut self, delta: Duration) {
+        self.clock += delta;
+    }
+
+    /// Adds a move to the recording, made at the current game time.
+    pub fn record(&mut self, shift: BoardShift) {
+        let at_ms = self.clock.as_millis() as u64;
+        self.undone.clear();
+        self.saved = false;
+        if let Some(replay) = self.replay.as_mut() {
+            replay.moves.push(ReplayMove { at_ms, shift });
+        }
+    }
+
+    pub fn save(&mut self) {
+        let Some(replay) = &self.replay else {
+            return;
+        };
+        if replay.moves.is_empty() || self.saved {
+            return;
+        }
+
+        match persistence::write(&self.file_name, replay) {
+            Ok(()) => self.saved = true,
+            Err(err) => warn!("could not save {}: {err}", self.file_name),
+        }
+    }
+}
+
+/// Saves the previous game's recording, if it got anywhere, and starts a
+/// new one for the game that was just set up.
+fn start_recording(mut recorder: ResMut<Recorder>, game: Res<Game>, spawns: Res<TileSpawns>) {
+    recorder.save();
+    recorder.start(Replay::new(
+        game.seed,
+        game.grid.width(),
+        game.grid.height(),
+        (**spawns).clone(),
+    ));
+}
+
+fn tick_recording(mut recorder: ResMut<Recorder>, time: Res<Time>) {
+    recorder.tick(time.delta());
+}
+
+fn record_move(mut recorder: ResMut<Recorder>, mut shifted_reader: EventReader<BoardShiftedEvent>) {
+    for BoardShiftedEvent(shift) in shifted_reader.read() {
+        recorder.record(*shift);
+    }
+}
+
+/// Keeps the recording in step with the move count after an undo or redo.
+fn follow_history(mut recorder: ResMut<Recorder>, game: Res<Game>) {
+    let recorder = &mut *recorder;
+    let Some(replay) = recorder.replay.as_mut() else {
+        return;
+    };
+
+    let moves = game.moves as usize;
+    while replay.moves.len() > moves {
+        let undone = replay
+            .moves
+            .pop()
+            .expect("there are more moves than `moves`");
+        recorder.undone.push(undone);
+        recorder.saved = false;
+    }
+    while replay.moves.len() < moves {
+        match recorder.undone.pop() {
+            Some(redone) => {
+                replay.moves.push(redone);
+                recorder.saved = false;
+            }
+            None => break,
+        }
+    }
+}
+
+fn save_recording(mut recorder: ResMut<Recorder>) {
+    recorder.save();
+}
+
+/// The replay being watched.
+#[derive(Resource)]
+pub struct ReplayPlayer {
+    replay: Replay,
+    /// How many moves have been played so far.
+    cursor: usize,
+    paused: bool,
+    speed: usize,
+    clock: Duration,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        ReplayPlayer {
+            replay,
+            cursor: 0,
+            paused: false,
+            speed: 2,
+            clock: Duration::ZERO,
+        }
+    }
+
+    /// The board the replay was recorded on.
+    pub fn board_size(&self) -> (u8, u8) {
+        (self.replay.width, self.replay.height)
+    }
+
+    fn speed(&self) -> f32 {
+        SPEEDS[self.speed]
+    }
+
+    /// Jumps to just after the first `cursor` moves.
+    fn seek(&mut self, cursor: usize) -> ReplayPosition {
+        self.cursor = cursor;
+        self.clock = match cursor {
+            0 => Duration::ZERO,
+            _ => Duration::from_millis(self.replay.moves[cursor - 1].at_ms),
+        };
+        self.replay.position_after(cursor)
+    }
+}
+
+/// Reads and checks the replay to watch. It's loaded before the app starts,
+/// so a file that can't be played never gets as far as touching the saved
+/// game.
+pub fn load_replay(path: &Path) -> Result<Replay, String> {
+    let replay = persistence::read_path::<Replay>(path)
+        .map_err(|err| format!("can't play {}: {err}", path.display()))?;
+
+    if replay.version > REPLAY_VERSION {
+        return Err(format!(
+            "can't play {}: replay version {} is newer than this build understands",
+            path.display(),
+            replay.version
+        ));
+    }
+    if !BOARD_SIZE_RANGE.contains(&replay.width) || !BOARD_SIZE_RANGE.contains(&replay.height) {
+        return Err(format!(
+            "can't play {}: unsupported board size {}x{}",
+            path.display(),
+            replay.width,
+            replay.height
+        ));
+    }
+
+    Ok(replay)
+}
+
+fn start_playback(
+    mut commands: Commands,
+    player: Option<ResMut<ReplayPlayer>>,
+    mut game: ResMut<Game>,
+    mut rng: ResMut<GameRng>,
+    mut run_state: ResMut<NextState<RunState>>,
+    mut restored: EventWriter<GridRestoredEvent>,
+) {
+    let Some(mut player) = player else {
+        return;
+    };
+
+    commands.insert_resource(TileSpawns(player.replay.spawns.clone()));
+    let start = player.seek(0);
+
+    game.score = 0;
+    game.moves = 0;
+    game.seed = player.replay.seed;
+    game.won = false;
+    game.hints_used = 0;
+    game.new_best = false;
+    game.grid = start.grid;
+    rng.0 = start.rng;
+    run_state.set(RunState::Replay);
+    restored.send(GridRestoredEvent);
+}
+
+fn replay_controls(
+    input: Res<ButtonInput<KeyCode>>,
+    mut player: ResMut<ReplayPlayer>,
+    mut game: ResMut<Game>,
+    mut rng: ResMut<GameRng>,
+    mut shift_writer: EventWriter<ShiftRequestEvent>,
+    mut restored: EventWriter<GridRestoredEvent>,
+) {
+    if input.just_pressed(KeyCode::Space) {
+        player.paused = !player.paused;
+    }
+    if input.just_pressed(KeyCode::ArrowUp) {
+        player.speed = (player.speed + 1).min(SPEEDS.len() - 1);
+    }
+    if input.just_pressed(KeyCode::ArrowDown) {
+        player.speed = player.speed.saturating_sub(1);
+    }
+
+    if input.just_pressed(KeyCode::ArrowRight) {
+        player.paused = true;
+        if let Some(next) = player.replay.moves.get(player.cursor).cloned() {
+            player.cursor += 1;
+            player.clock = Duration::from_millis(next.at_ms);
+            shift_writer.send(ShiftRequestEvent(next.shift));
+        }
+    } else if input.just_pressed(KeyCode::ArrowLeft) && player.cursor > 0 {
+        player.paused = true;
+        let cursor = player.cursor - 1;
+        let position = player.seek(cursor);
+
+        game.score = position.score;
+        game.moves = position.moves as u32;
+        game.grid = position.grid;
+        rng.0 = position.rng;
+        restored.send(GridRestoredEvent);
+    }
+}
+
+/// Plays moves as their timestamps come up, at most one per frame.
+fn replay_autoplay(
+    mut player: ResMut<ReplayPlayer>,
+    time: Res<Time>,
+    mut shift_writer: EventWriter<ShiftRequestEvent>,
+) {
+    if player.paused {
+        return;
+    }
+
+    let speed = player.speed();
+    player.clock += time.delta().mul_f32(speed);
+
+    if let Some(next) = player.replay.moves.get(player.cursor) {
+        if Duration::from_millis(next.at_ms) <= player.clock {
+            shift_writer.send(ShiftRequestEvent(next.shift));
+            player.cursor += 1;
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct ReplayHud;
+
+fn spawn_replay_hud(mut commands: Commands, font_spec: Res<FontSpec>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: font_spec.family.clone(),
+                font_size: 15.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(20.0),
+            left: Val::Px(50.0),
+            ..default()
+        }),
+        ReplayHud,
+    ));
+}
+
+fn replay_hud(player: Res<ReplayPlayer>, mut query_hud: Query<&mut Text, With<ReplayHud>>) {
+    if !player.is_changed() {
+        return;
+    }
+
+    for mut text in query_hud.iter_mut() {
+        text.sections[0].value = format!(
+            "Replay: move {}/{} at {}x{}  -  Space: pause, Left/Right: step, Up/Down: speed",
+            player.cursor,
+            player.replay.moves.len(),
+            player.speed(),
+            if player.paused { ", paused" } else { "" },
+        );
+    }
+}
@@ -0,0 +1,164 @@
+//! Recorded games, replayable move for move.
+//!
+//! A replay is stored as RON and only holds what's needed to rebuild the
+//! game: the RNG seed, the board size, the spawn weights and every move that
+//! changed the board, stamped with the milliseconds since the game started.
+//!
+//! ```ron
+//! (
+//!     version: 1,
+//!     seed: 42,
+//!     width: 4,
+//!     height: 4,
+//!     spawns: [(2, 9), (4, 1)],
+//!     moves: [
+//!         (at_ms: 850, shift: Left),
+//!         (at_ms: 1320, shift: Up),
+//!     ],
+//! )
+//! ```
+//!
+//! The RNG is a `ChaCha8Rng` seeded with `seed`. A game starts with
+//! [`STARTING_TILES`] tiles spawned into an empty grid, and one more tile is
+//! spawned after each move, so the same moves always produce the same board.
+
+use crate::engine::{BoardShift, Grid, SpawnDistribution, STARTING_TILES};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+/// Bumped whenever the replay format changes in a way older builds can't read.
+pub const REPLAY_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplayMove {
+    pub at_ms: u64,
+    pub shift: BoardShift,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    pub width: u8,
+    pub height: u8,
+    pub spawns: SpawnDistribution,
+    pub moves: Vec<ReplayMove>,
+}
+
+/// A game rebuilt from a replay, part way through.
+#[derive(Debug, Clone)]
+pub struct ReplayPosition {
+    pub grid: Grid,
+    pub score: u32,
+    /// How many moves have been played to get here.
+    pub moves: usize,
+    /// The RNG as it is before the next spawn.
+    pub rng: ChaCha8Rng,
+}
+
+impl Replay {
+    pub fn new(seed: u64, width: u8, height: u8, spawns: SpawnDistribution) -> Self {
+        Replay {
+            version: REPLAY_VERSION,
+            seed,
+            width,
+            height,
+            spawns,
+            moves: Vec::new(),
+        }
+    }
+
+    /// The board before any move, with its starting tiles.
+    pub fn start(&self) -> ReplayPosition {
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        let mut grid = Grid::new(self.width, self.height);
+        for _ in 0..STARTING_TILES {
+            grid.spawn_random(&mut rng, &self.spawns);
+        }
+
+        ReplayPosition {
+            grid,
+            score: 0,
+            moves: 0,
+            rng,
+        }
+    }
+
+    /// Plays the first `count` moves from the start.
+    pub fn position_after(&self, count: usize) -> ReplayPosition {
+        let mut position = self.start();
+        for replay_move in self.moves.iter().take(count) {
+            let outcome = position.grid.apply_move(replay_move.shift);
+            if outcome.changed() {
+                position.grid.spawn_random(&mut position.rng, &self.spawns);
+            }
+            position.score += outcome.score;
+            position.moves += 1;
+        }
+
+        position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHIFTS: [BoardShift; 4] = [
+        BoardShift::Down,
+        BoardShift::Left,
+        BoardShift::Right,
+        BoardShift::Up,
+    ];
+
+    /// Plays up to `count` moves on a seeded game, recording them as it goes.
+    /// Returns the replay along with the game as it was actually played.
+    fn record(seed: u64, count: usize) -> (Replay, ReplayPosition) {
+        let mut replay = Replay::new(seed, 4, 4, SpawnDistribution::default());
+        let mut played = replay.start();
+
+        for at_ms in 0..count as u64 {
+            let Some((shift, outcome)) = SHIFTS.into_iter().find_map(|shift| {
+                let mut next = played.grid.clone();
+                let outcome = next.apply_move(shift);
+                outcome.changed().then_some((shift, outcome))
+            }) else {
+                break;
+            };
+
+            played.grid.apply_move(shift);
+            played.grid.spawn_random(&mut played.rng, &replay.spawns);
+            played.score += outcome.score;
+            played.moves += 1;
+            replay.moves.push(ReplayMove { at_ms, shift });
+        }
+
+        (replay, played)
+    }
+
+    fn assert_same(a: &ReplayPosition, b: &ReplayPosition) {
+        assert_eq!(a.grid, b.grid);
+        assert_eq!(a.score, b.score);
+        assert_eq!(a.moves, b.moves);
+        assert_eq!(a.rng, b.rng);
+    }
+
+    #[test]
+    fn position_after_rebuilds_the_game_played() {
+        let (replay, played) = record(42, 30);
+        assert_eq!(replay.moves.len(), 30);
+
+        assert_same(&replay.position_after(replay.moves.len()), &played);
+    }
+
+    #[test]
+    fn position_after_is_repeatable() {
+        let (replay, _) = record(7, 20);
+
+        for count in [0, 1, 10, 20] {
+            assert_same(&replay.position_after(count), &replay.position_after(count));
+        }
+        assert_same(&replay.position_after(0), &replay.start());
+    }
+}
@@ -0,0 +1,137 @@
+//! The `simulate` subcommand: plays games headless and reports on them.
+
+use std::{
+    fmt::Write as _,
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    str::FromStr,
+};
+use two_oh_four_eight::simulation::{Report, Simulation};
+
+/// How the report is written out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    /// A summary for reading in the terminal.
+    #[default]
+    Table,
+    /// The summary and every game's result.
+    Json,
+    /// One row per game.
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(Format::Table),
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Simulate {
+    pub simulation: Simulation,
+    pub format: Format,
+    /// Where to write the report, stdout when unset.
+    pub output: Option<PathBuf>,
+}
+
+pub fn run(simulate: &Simulate) -> io::Result<()> {
+    let report = simulate.simulation.run();
+
+    let text = match simulate.format {
+        Format::Table => table(&report),
+        Format::Json => serde_json::to_string_pretty(&report)? + "\n",
+        Format::Csv => csv(&report),
+    };
+
+    match &simulate.output {
+        Some(path) => fs::write(path, text),
+        None => io::stdout().write_all(text.as_bytes()),
+    }
+}
+
+fn table(report: &Report) -> String {
+    let mut out = String::new();
+    let scores = &report.scores;
+
+    // Writing to a String can't fail
+    let _ = writeln!(
+        out,
+        "{} games of {}x{} with {} (seeds {} to {}), {} {}",
+        report.games,
+        report.width,
+        report.height,
+        report.strategy,
+        report.seed,
+        report
+            .seed
+            .wrapping_add((report.games as u64).saturating_sub(1)),
+        report.threads,
+        if report.threads == 1 {
+            "thread"
+        } else {
+            "threads"
+        },
+    );
+    let _ = writeln!(
+        out,
+        "{:.2}s, {:.1} games/s\n",
+        report.elapsed_secs, report.games_per_sec
+    );
+    let _ = writeln!(
+        out,
+        "win rate       {:>6.1}%  (reached {})",
+        report.win_rate * 100.0,
+        report.win_target
+    );
+    let _ = writeln!(out, "average moves  {:>7.1}", report.average_moves);
+    let _ = writeln!(out, "\nscore");
+    let _ = writeln!(out, "  mean    {:>9.1}", scores.mean);
+    for (label, score) in [
+        ("min", scores.min),
+        ("p25", scores.p25),
+        ("median", scores.median),
+        ("p75", scores.p75),
+        ("p90", scores.p90),
+        ("max", scores.max),
+    ] {
+        let _ = writeln!(out, "  {label:<7} {score:>7}");
+    }
+
+    let _ = writeln!(out, "\nhighest tile");
+    let widest = report.max_tiles.values().copied().max().unwrap_or(0).max(1);
+    for (tile, count) in report.max_tiles.iter() {
+        let bar = "#".repeat((count * 40).div_ceil(widest));
+        let share = *count as f64 / report.games as f64 * 100.0;
+        let _ = writeln!(out, "  {tile:>6} {count:>6} {share:>5.1}%  {bar}");
+    }
+
+    out
+}
+
+fn csv(report: &Report) -> String {
+    let mut out = String::from("seed,strategy,width,height,score,moves,max_tile,won\n");
+    for result in report.results.iter() {
+        let _ = writeln!(
+            out,
+            "{},{},{},{},{},{},{},{}",
+            result.seed,
+            report.strategy,
+            report.width,
+            report.height,
+            result.score,
+            result.moves,
+            result.max_tile,
+            result.won
+        );
+    }
+
+    out
+}
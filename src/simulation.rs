@@ -0,0 +1,377 @@
+//! Plays whole games without a window, to measure how well a strategy does.
+//!
+//! Game `i` of a run is seeded with `seed + i` and set up exactly like a
+//! game in the app (see [`Replay::start`]), so a run can be repeated and any
+//! game in it watched again with the same seed.
+
+use crate::{
+    ai::{Expectimax, Heuristics, SHIFTS},
+    engine::{BoardShift, Grid, SpawnDistribution},
+    replay::Replay,
+};
+use rand::{seq::SliceRandom, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How moves are picked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Strategy {
+    /// Any move that changes the board.
+    Random,
+    /// The move that scores the most points right now.
+    Greedy,
+    /// Down, then left, then right, then up, which piles the tiles into the
+    /// bottom left corner.
+    Corner,
+    /// The computer player's search, see [`Expectimax`].
+    Expectimax,
+}
+
+impl Strategy {
+    pub const ALL: [Strategy; 4] = [
+        Strategy::Random,
+        Strategy::Greedy,
+        Strategy::Corner,
+        Strategy::Expectimax,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Strategy::Random => "random",
+            Strategy::Greedy => "greedy",
+            Strategy::Corner => "corner",
+            Strategy::Expectimax => "expectimax",
+        }
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Strategy::ALL
+            .into_iter()
+            .find(|strategy| strategy.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = Strategy::ALL.iter().map(Strategy::name).collect();
+                format!(
+                    "unknown strategy {s:?}, expected one of {}",
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// What to play and how many times.
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    pub strategy: Strategy,
+    pub games: usize,
+    /// The seed of the first game, each game after it adds one.
+    pub seed: u64,
+    /// How many games are played at once.
+    pub threads: usize,
+    pub width: u8,
+    pub height: u8,
+    /// The tile value that counts as a win.
+    pub win_target: u32,
+    /// How many moves ahead the expectimax strategy searches.
+    pub depth: u8,
+    pub spawns: SpawnDistribution,
+    pub heuristics: Heuristics,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Simulation {
+            strategy: Strategy::Random,
+            games: 100,
+            seed: 0,
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+            width: 4,
+            height: 4,
+            win_target: 2048,
+            depth: 2,
+            spawns: SpawnDistribution::default(),
+            heuristics: Heuristics::default(),
+        }
+    }
+}
+
+/// How a single game ended.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GameResult {
+    pub seed: u64,
+    pub score: u32,
+    pub moves: u32,
+    pub max_tile: u32,
+    pub won: bool,
+}
+
+impl Simulation {
+    /// Plays every game, spread over `threads` threads. Results come back
+    /// in seed order however the games were scheduled.
+    pub fn run(&self) -> Report {
+        let started = Instant::now();
+        let next = AtomicUsize::new(0);
+        let threads = self.threads.clamp(1, self.games.max(1));
+
+        let mut results: Vec<(usize, GameResult)> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut played = Vec::new();
+                        loop {
+                            let index = next.fetch_add(1, Ordering::Relaxed);
+                            if index >= self.games {
+                                break played;
+                            }
+                            played.push((index, self.play(self.seed.wrapping_add(index as u64))));
+                        }
+                    })
+                })
+                .collect();
+
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("a simulated game panicked"))
+                .collect()
+        });
+        results.sort_by_key(|(index, _)| *index);
+
+        Report::new(
+            self,
+            threads,
+            started.elapsed(),
+            results.into_iter().map(|(_, result)| result).collect(),
+        )
+    }
+
+    /// Plays one game through to the end.
+    pub fn play(&self, seed: u64) -> GameResult {
+        let replay = Replay::new(seed, self.width, self.height, self.spawns.clone());
+        let start = replay.start();
+        let (mut grid, mut rng) = (start.grid, start.rng);
+
+        // The strategy's own choices come from a different stream, so they
+        // don't change which tiles spawn
+        let mut choices = ChaCha8Rng::seed_from_u64(seed);
+        choices.set_stream(1);
+
+        let (mut score, mut moves) = (0, 0);
+        while let Some(shift) = self.pick(&grid, &mut choices) {
+            score += grid.apply_move(shift).score;
+            moves += 1;
+            grid.spawn_random(&mut rng, &self.spawns);
+        }
+
+        let max_tile = grid.max_tile().unwrap_or(0);
+        GameResult {
+            seed,
+            score,
+            moves,
+            max_tile,
+            won: max_tile >= self.win_target,
+        }
+    }
+
+    /// The strategy's next move, `None` once no move changes the board.
+    fn pick(&self, grid: &Grid, rng: &mut ChaCha8Rng) -> Option<BoardShift> {
+        let outcomes = SHIFTS.iter().filter_map(|shift| {
+            let mut next = grid.clone();
+            let outcome = next.apply_move(*shift);
+            outcome.changed().then_some((*shift, outcome.score))
+        });
+
+        match self.strategy {
+            Strategy::Random => {
+                let shifts: Vec<_> = outcomes.map(|(shift, _)| shift).collect();
+                shifts.choose(rng).copied()
+            }
+            // `max_by_key` keeps the last of equals, so go backwards to keep
+            // the first
+            Strategy::Greedy => outcomes
+                .collect::<Vec<_>>()
+                .into_iter()
+                .rev()
+                .max_by_key(|(_, score)| *score)
+                .map(|(shift, _)| shift),
+            Strategy::Corner => {
+                let available: Vec<_> = outcomes.map(|(shift, _)| shift).collect();
+                [
+                    BoardShift::Down,
+                    BoardShift::Left,
+                    BoardShift::Right,
+                    BoardShift::Up,
+                ]
+                .into_iter()
+                .find(|shift| available.contains(shift))
+            }
+            Strategy::Expectimax => {
+                Expectimax::new(&self.spawns, &self.heuristics, self.depth).best_move(grid)
+            }
+        }
+    }
+}
+
+/// Everything measured over a run.
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub strategy: Strategy,
+    pub games: usize,
+    pub seed: u64,
+    pub threads: usize,
+    pub width: u8,
+    pub height: u8,
+    pub win_target: u32,
+    pub elapsed_secs: f64,
+    pub games_per_sec: f64,
+    /// The share of games that reached `win_target`, from 0 to 1.
+    pub win_rate: f64,
+    pub average_moves: f64,
+    pub scores: ScoreSummary,
+    /// How many games ended with each highest tile.
+    pub max_tiles: BTreeMap<u32, usize>,
+    pub results: Vec<GameResult>,
+}
+
+/// The spread of final scores.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ScoreSummary {
+    pub mean: f64,
+    pub min: u32,
+    pub p25: u32,
+    pub median: u32,
+    pub p75: u32,
+    pub p90: u32,
+    pub max: u32,
+}
+
+impl ScoreSummary {
+    fn new(scores: &mut [u32]) -> Self {
+        if scores.is_empty() {
+            return ScoreSummary::default();
+        }
+
+        scores.sort_unstable();
+        let percentile = |p: usize| scores[(scores.len() - 1) * p / 100];
+        ScoreSummary {
+            mean: scores.iter().map(|score| f64::from(*score)).sum::<f64>() / scores.len() as f64,
+            min: percentile(0),
+            p25: percentile(25),
+            median: percentile(50),
+            p75: percentile(75),
+            p90: percentile(90),
+            max: percentile(100),
+        }
+    }
+}
+
+impl Report {
+    fn new(
+        simulation: &Simulation,
+        threads: usize,
+        elapsed: Duration,
+        results: Vec<GameResult>,
+    ) -> Self {
+        let games = results.len();
+        let per_game = |total: f64| {
+            if games == 0 {
+                0.0
+            } else {
+                total / games as f64
+            }
+        };
+
+        let mut max_tiles = BTreeMap::new();
+        for result in results.iter() {
+            *max_tiles.entry(result.max_tile).or_default() += 1;
+        }
+        let mut scores: Vec<u32> = results.iter().map(|result| result.score).collect();
+
+        Report {
+            strategy: simulation.strategy,
+            games,
+            seed: simulation.seed,
+            threads,
+            width: simulation.width,
+            height: simulation.height,
+            win_target: simulation.win_target,
+            elapsed_secs: elapsed.as_secs_f64(),
+            games_per_sec: games as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+            win_rate: per_game(results.iter().filter(|result| result.won).count() as f64),
+            average_moves: per_game(results.iter().map(|result| f64::from(result.moves)).sum()),
+            scores: ScoreSummary::new(&mut scores),
+            max_tiles,
+            results,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn simulation(strategy: Strategy) -> Simulation {
+        Simulation {
+            strategy,
+            games: 4,
+            depth: 1,
+            ..Simulation::default()
+        }
+    }
+
+    #[test]
+    fn games_repeat_for_a_seed() {
+        for strategy in Strategy::ALL {
+            let simulation = simulation(strategy);
+            assert_eq!(simulation.play(11), simulation.play(11));
+        }
+    }
+
+    #[test]
+    fn results_dont_depend_on_threads() {
+        let single = Simulation {
+            threads: 1,
+            ..simulation(Strategy::Random)
+        };
+        let many = Simulation {
+            threads: 4,
+            ..simulation(Strategy::Random)
+        };
+
+        assert_eq!(single.run().results, many.run().results);
+    }
+
+    #[test]
+    fn seeds_wrap_around() {
+        let simulation = Simulation {
+            games: 3,
+            seed: u64::MAX - 1,
+            ..simulation(Strategy::Corner)
+        };
+
+        let seeds: Vec<_> = simulation
+            .run()
+            .results
+            .iter()
+            .map(|result| result.seed)
+            .collect();
+        assert_eq!(seeds, [u64::MAX - 1, u64::MAX, 0]);
+    }
+}
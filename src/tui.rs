@@ -0,0 +1,293 @@
+//! Plays in the terminal instead of a window, with `--tui`.
+//!
+//! The rules, profile and save file are the same as the graphical game's,
+//! so a game started in one can be finished in the other.
+
+use crate::{
+    cli::Cli,
+    colours,
+    persistence::{self, Profile, SavedGame},
+    recorder::Recorder,
+    Game, GameRng, RunState, Settings,
+};
+use bevy::prelude::Color;
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::{self, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
+    terminal::{self, ClearType},
+};
+use std::{
+    io::{self, Write},
+    time::Instant,
+};
+use two_oh_four_eight::{
+    engine::{BoardShift, SpawnDistribution, STARTING_TILES},
+    replay::Replay,
+};
+
+/// Characters across and lines down taken up by one tile.
+const CELL_WIDTH: usize = 8;
+const CELL_HEIGHT: usize = 3;
+
+pub fn run(cli: &Cli) -> io::Result<()> {
+    let mut session = Session::start(cli);
+    let _terminal = RawTerminal::enter()?;
+    let mut stdout = io::stdout();
+
+    loop {
+        session.draw(&mut stdout)?;
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => break,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
+            KeyCode::Char('n') => session.new_game(),
+            code => {
+                if let Some(shift) = key_to_shift(code) {
+                    session.shift(shift);
+                }
+            }
+        }
+    }
+
+    session.save();
+    Ok(())
+}
+
+fn key_to_shift(code: KeyCode) -> Option<BoardShift> {
+    match code {
+        KeyCode::Left | KeyCode::Char('a') => Some(BoardShift::Left),
+        KeyCode::Right | KeyCode::Char('d') => Some(BoardShift::Right),
+        KeyCode::Up | KeyCode::Char('w') => Some(BoardShift::Up),
+        KeyCode::Down | KeyCode::Char('s') => Some(BoardShift::Down),
+        _ => None,
+    }
+}
+
+/// Raw mode on the alternate screen, put back how it was found when dropped,
+/// including on a panic.
+struct RawTerminal;
+
+impl RawTerminal {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(RawTerminal)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Everything the graphical game keeps in resources, held directly.
+struct Session {
+    game: Game,
+    rng: GameRng,
+    spawns: SpawnDistribution,
+    recorder: Recorder,
+    profile: Profile,
+    settings: Settings,
+    /// The `--seed` flag, which seeds every new game.
+    seed: Option<u64>,
+    run_state: RunState,
+    message: Option<String>,
+    /// When the recording clock was last moved on.
+    last_tick: Instant,
+}
+
+impl Session {
+    fn start(cli: &Cli) -> Self {
+        let profile = persistence::load_profile_or_default();
+        let mut session = Session {
+            game: Game::default(),
+            rng: GameRng::default(),
+            spawns: SpawnDistribution::default(),
+            recorder: Recorder::default(),
+            settings: profile.settings.clone(),
+            profile,
+            seed: cli.seed,
+            run_state: RunState::Playing,
+            message: None,
+            last_tick: Instant::now(),
+        };
+
+        match persistence::game_to_resume(cli.seed) {
+            Some(saved) => session.resume(saved),
+            None => session.new_game(),
+        }
+
+        session
+    }
+
+    fn resume(&mut self, saved: SavedGame) {
+        let run_state;
+        (self.game, self.rng, run_state) =
+            Game::from_saved(saved, &mut self.settings, &self.profile, &mut self.recorder);
+        // There's no win overlay to dismiss here, so only a finished game
+        // stays finished
+        self.run_state = match run_state {
+            RunState::GameOver => RunState::GameOver,
+            _ => RunState::Playing,
+        };
+        self.message = None;
+        self.last_tick = Instant::now();
+    }
+
+    fn new_game(&mut self) {
+        self.recorder.save();
+
+        let (width, height) = self.settings.board_size();
+        (self.game, self.rng) =
+            Game::new_seeded((width, height), self.seed, &self.settings, &self.profile);
+        for _ in 0..STARTING_TILES {
+            self.game.grid.spawn_random(&mut self.rng.0, &self.spawns);
+        }
+
+        self.recorder.start(Replay::new(
+            self.game.seed,
+            width,
+            height,
+            self.spawns.clone(),
+        ));
+        self.run_state = RunState::Playing;
+        self.message = None;
+        self.last_tick = Instant::now();
+    }
+
+    fn shift(&mut self, shift: BoardShift) {
+        if self.run_state != RunState::Playing {
+            return;
+        }
+
+        let now = Instant::now();
+        self.recorder.tick(now - self.last_tick);
+        self.last_tick = now;
+
+        let outcome = self.game.grid.apply_move(shift);
+        if !outcome.changed() {
+            return;
+        }
+
+        self.recorder.record(shift);
+        self.game.moves += 1;
+        for merge in outcome.merges.iter() {
+            self.game.add_score(merge.value);
+        }
+        self.game.grid.spawn_random(&mut self.rng.0, &self.spawns);
+        self.message = None;
+        self.profile
+            .record(self.settings.board_size(), self.game.score_best);
+
+        if !self.game.won && self.game.grid.max_tile() >= Some(self.settings.win_target) {
+            self.game.won = true;
+            self.message = Some(format!(
+                "You reached the {} tile! Keep going.",
+                self.settings.win_target
+            ));
+        }
+
+        if self.game.grid.is_game_over() {
+            self.run_state = RunState::GameOver;
+            self.recorder.save();
+        }
+
+        self.save();
+    }
+
+    fn save(&mut self) {
+        self.profile.settings = self.settings.clone();
+        persistence::write_profile(&self.profile);
+        persistence::write_game(&self.game, &self.rng, &self.run_state, &self.recorder);
+    }
+
+    fn draw(&self, out: &mut impl Write) -> io::Result<()> {
+        let grid = &self.game.grid;
+        let board_width = usize::from(grid.width()) * (CELL_WIDTH + 1) + 1;
+        let board = term_colour(colours::BOARD);
+
+        queue!(
+            out,
+            terminal::Clear(ClearType::All),
+            cursor::MoveTo(0, 0),
+            Print(format!(
+                "Two-oh-four-eight   Score: {}   Best: {}{}\r\n\r\n",
+                self.game.score,
+                self.game.score_best,
+                if self.game.new_best {
+                    "   New best!"
+                } else {
+                    ""
+                },
+            )),
+        )?;
+
+        let edge = " ".repeat(board_width);
+        queue!(
+            out,
+            SetBackgroundColor(board),
+            Print(&edge),
+            ResetColor,
+            Print("\r\n")
+        )?;
+
+        for y in (0..grid.height()).rev() {
+            for line in 0..CELL_HEIGHT {
+                queue!(out, SetBackgroundColor(board), Print(" "))?;
+                for x in 0..grid.width() {
+                    let value = grid.get((x, y));
+                    let background = value.map_or(colours::TILE_PLACEHOLDER, colours::tile);
+                    let text = match value {
+                        Some(value) if line == CELL_HEIGHT / 2 => value.to_string(),
+                        _ => String::new(),
+                    };
+                    queue!(
+                        out,
+                        SetBackgroundColor(term_colour(background)),
+                        SetForegroundColor(term_colour(colours::text_on(background))),
+                        Print(format!("{text:^CELL_WIDTH$}")),
+                        SetBackgroundColor(board),
+                        Print(" "),
+                    )?;
+                }
+                queue!(out, ResetColor, Print("\r\n"))?;
+            }
+            queue!(
+                out,
+                SetBackgroundColor(board),
+                Print(&edge),
+                ResetColor,
+                Print("\r\n")
+            )?;
+        }
+
+        let status = match (&self.run_state, &self.message) {
+            (RunState::GameOver, _) => "Game over!",
+            (_, Some(message)) => message.as_str(),
+            _ => "",
+        };
+        queue!(
+            out,
+            Print(format!("\r\n{status}\r\n")),
+            Print("Arrows/WASD: move   n: new game   q: quit\r\n"),
+        )?;
+
+        out.flush()
+    }
+}
+
+fn term_colour(colour: Color) -> style::Color {
+    let [r, g, b, _] = colour.as_rgba_u8();
+    style::Color::Rgb { r, g, b }
+}
@@ -1,23 +1,178 @@
+mod confirm;
+mod focus;
 mod styles;
 
-use crate::{colours, FontSpec, Game, RunState};
-use bevy::prelude::*;
+pub use confirm::{ConfirmRequest, Confirmable, Confirming};
+pub use focus::{MenuEvent, MenuFocus};
+
+use crate::{
+    board_size, colours,
+    controls::{Action, KeyBindings, Preset, Rebinding},
+    history::{History, HistoryEvent},
+    recorder::{Recorder, ReplayPlayer},
+    FontSpec, Game, RunState, Settings,
+};
+use bevy::{app::AppExit, prelude::*};
+use bevy_easings::*;
+use itertools::Itertools;
+use std::time::Duration;
 
 pub struct GameUIPlugin;
 
 impl Plugin for GameUIPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, (setup_ui,))
-            .add_systems(Update, (scoreboard, button_interaction, button_text));
+        app.add_plugins(confirm::ConfirmPlugin)
+            .init_resource::<MenuFocus>()
+            .add_event::<MenuEvent>()
+            .add_systems(Startup, (setup_ui,))
+            .add_systems(
+                Update,
+                (
+                    scoreboard,
+                    seed_display,
+                    celebrate_best.run_if(resource_changed::<Game>),
+                    (
+                        focus::menu_navigation,
+                        button_interaction,
+                        focus::focus_highlight,
+                    )
+                        .chain(),
+                    button_text,
+                    show_controls_screen.run_if(resource_added::<Rebinding>),
+                    despawn_with::<ControlsScreen>.run_if(resource_removed::<Rebinding>()),
+                    controls_message.run_if(resource_exists_and_changed::<Rebinding>),
+                    show_settings_screen.run_if(resource_added::<SettingsOpen>),
+                    despawn_with::<SettingsScreen>.run_if(resource_removed::<SettingsOpen>()),
+                ),
+            )
+            .add_systems(OnEnter(RunState::Won), show_win_overlay)
+            .add_systems(OnExit(RunState::Won), despawn_with::<WinOverlay>)
+            .add_systems(OnEnter(RunState::GameOver), show_game_over_overlay)
+            .add_systems(OnExit(RunState::GameOver), despawn_with::<GameOverOverlay>)
+            .add_systems(OnEnter(RunState::Paused), show_pause_overlay)
+            .add_systems(
+                OnExit(RunState::Paused),
+                (despawn_with::<PauseOverlay>, close_settings),
+            );
     }
 }
 
+/// What happens when a button is pressed.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ButtonAction {
+    /// The header button: ends the current game, or starts a new one once
+    /// it is over.
+    Toggle,
+    /// Carry on playing after reaching the win target.
+    KeepGoing,
+    TryAgain,
+    /// Takes back the move that ended the game.
+    Undo,
+    Resume,
+    /// Opens the settings screen.
+    Settings,
+    /// Flips a setting on or off.
+    Setting(SettingToggle),
+    CloseSettings,
+    Quit,
+    /// Answers the confirmation dialog.
+    Confirm,
+    Cancel,
+    /// Cycles through the available board sizes, starting a new game.
+    BoardSize,
+    /// Opens the screen for changing key bindings.
+    Controls,
+    /// Waits for a key to bind to the action.
+    Rebind(Action),
+    Preset(Preset),
+    CloseControls,
+}
+
+#[derive(Component)]
+pub struct WinOverlay;
+
+#[derive(Component)]
+pub struct PauseOverlay;
+
+#[derive(Component)]
+pub struct GameOverOverlay;
+
+/// The settings screen is open while this resource exists.
+#[derive(Resource, Default)]
+pub struct SettingsOpen;
+
+#[derive(Component)]
+pub struct SettingsScreen;
+
+/// The settings that can be switched on and off from the settings screen.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SettingToggle {
+    Animations,
+    FastForward,
+    PauseOnFocusLoss,
+    ConfirmDestructive,
+}
+
+impl SettingToggle {
+    const ALL: [SettingToggle; 4] = [
+        SettingToggle::Animations,
+        SettingToggle::FastForward,
+        SettingToggle::PauseOnFocusLoss,
+        SettingToggle::ConfirmDestructive,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            SettingToggle::Animations => "Animations",
+            SettingToggle::FastForward => "Skip animations for queued moves",
+            SettingToggle::PauseOnFocusLoss => "Pause when the window loses focus",
+            SettingToggle::ConfirmDestructive => "Ask before ending a game",
+        }
+    }
+
+    fn is_on(&self, settings: &Settings) -> bool {
+        match self {
+            SettingToggle::Animations => settings.animations.enabled,
+            SettingToggle::FastForward => settings.fast_forward,
+            SettingToggle::PauseOnFocusLoss => settings.pause_on_focus_loss,
+            SettingToggle::ConfirmDestructive => settings.confirm_destructive,
+        }
+    }
+
+    fn flip(&self, settings: &mut Settings) {
+        let on = !self.is_on(settings);
+        match self {
+            SettingToggle::Animations => settings.animations.enabled = on,
+            SettingToggle::FastForward => settings.fast_forward = on,
+            SettingToggle::PauseOnFocusLoss => settings.pause_on_focus_loss = on,
+            SettingToggle::ConfirmDestructive => settings.confirm_destructive = on,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct ControlsScreen;
+
+/// Explains why a key wasn't accepted on the controls screen.
+#[derive(Component)]
+pub struct ControlsMessage;
+
 #[derive(Component)]
 pub struct ScoreDisplay;
 
 #[derive(Component)]
 pub struct BestScoreDisplay;
 
+#[derive(Component)]
+pub struct SeedDisplay;
+
+/// The best score's box and its label, lit up when the best is beaten.
+#[derive(Component)]
+pub struct BestScoreBox;
+
+#[derive(Component)]
+pub struct BestScoreLabel;
+
 fn setup_ui(mut commands: Commands, font_spec: Res<FontSpec>) {
     commands
         .spawn(NodeBundle {
@@ -32,14 +187,35 @@ fn setup_ui(mut commands: Commands, font_spec: Res<FontSpec>) {
             ..default()
         })
         .with_children(|parent| {
-            parent.spawn(TextBundle::from_section(
-                "Two-oh-four-eight",
-                TextStyle {
-                    font: font_spec.family.clone(),
-                    font_size: 40.0,
-                    color: Color::WHITE,
-                },
-            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Two-oh-four-eight",
+                        TextStyle {
+                            font: font_spec.family.clone(),
+                            font_size: 40.0,
+                            color: Color::WHITE,
+                        },
+                    ));
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "<seed>",
+                            TextStyle {
+                                font: font_spec.family.clone(),
+                                font_size: 15.0,
+                                color: Color::WHITE,
+                            },
+                        ),
+                        SeedDisplay,
+                    ));
+                });
 
             parent
                 .spawn(NodeBundle {
@@ -89,13 +265,16 @@ fn setup_ui(mut commands: Commands, font_spec: Res<FontSpec>) {
                     // end scorebox
                     // best scorebox
                     parent
-                        .spawn(NodeBundle {
-                            style: styles::score_container_style(),
-                            background_color: BackgroundColor(colours::SCORE_BOX),
-                            ..default()
-                        })
+                        .spawn((
+                            NodeBundle {
+                                style: styles::score_container_style(),
+                                background_color: BackgroundColor(colours::SCORE_BOX),
+                                ..default()
+                            },
+                            BestScoreBox,
+                        ))
                         .with_children(|parent| {
-                            parent.spawn(
+                            parent.spawn((
                                 TextBundle::from_section(
                                     "Best",
                                     TextStyle {
@@ -105,7 +284,8 @@ fn setup_ui(mut commands: Commands, font_spec: Res<FontSpec>) {
                                     },
                                 )
                                 .with_text_justify(JustifyText::Center),
-                            );
+                                BestScoreLabel,
+                            ));
 
                             parent.spawn((
                                 TextBundle::from_section(
@@ -123,32 +303,422 @@ fn setup_ui(mut commands: Commands, font_spec: Res<FontSpec>) {
                     // end best scorebox
                 });
             parent
-                .spawn(ButtonBundle {
+                .spawn(NodeBundle {
                     style: Style {
-                        width: Val::Px(130.0),
-                        height: Val::Px(50.0),
-                        justify_content: JustifyContent::Center,
-                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(20.0),
                         ..default()
                     },
                     ..default()
                 })
                 .with_children(|parent| {
-                    parent.spawn(TextBundle {
-                        text: Text::from_section(
-                            "Button",
-                            TextStyle {
-                                font: font_spec.family.clone(),
-                                font_size: 20.0,
-                                color: Color::rgb(0.9, 0.9, 0.9),
-                            },
-                        ),
+                    spawn_button(parent, &font_spec, "Keys", ButtonAction::Controls);
+                    spawn_button(parent, &font_spec, "Size", ButtonAction::BoardSize);
+                    spawn_button(parent, &font_spec, "Button", ButtonAction::Toggle);
+                });
+        });
+}
+
+fn spawn_button(
+    parent: &mut ChildBuilder,
+    font_spec: &FontSpec,
+    label: &str,
+    action: ButtonAction,
+) {
+    spawn_styled_button(parent, font_spec, label, action, styles::button_style());
+}
+
+fn spawn_styled_button(
+    parent: &mut ChildBuilder,
+    font_spec: &FontSpec,
+    label: &str,
+    action: ButtonAction,
+    style: Style,
+) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style,
+                background_color: colours::button::NORMAL.into(),
+                ..default()
+            },
+            action,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle {
+                text: Text::from_section(
+                    label,
+                    TextStyle {
+                        font: font_spec.family.clone(),
+                        font_size: 20.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                    },
+                ),
+                ..default()
+            });
+        });
+}
+
+fn show_win_overlay(mut commands: Commands, font_spec: Res<FontSpec>, settings: Res<Settings>) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: styles::overlay_style(),
+                background_color: BackgroundColor(colours::OVERLAY),
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+            WinOverlay,
+            focus::Modal,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "You win!",
+                TextStyle {
+                    font: font_spec.family.clone(),
+                    font_size: 40.0,
+                    color: Color::WHITE,
+                },
+            ));
+            parent.spawn(TextBundle::from_section(
+                format!("You reached the {} tile", settings.win_target),
+                TextStyle {
+                    font: font_spec.family.clone(),
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                },
+            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        column_gap: Val::Px(20.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    spawn_button(parent, &font_spec, "Keep going", ButtonAction::KeepGoing);
+                    spawn_button(parent, &font_spec, "Try again", ButtonAction::TryAgain);
+                });
+        });
+}
+
+fn show_game_over_overlay(
+    mut commands: Commands,
+    font_spec: Res<FontSpec>,
+    game: Res<Game>,
+    recorder: Res<Recorder>,
+    history: Res<History>,
+) {
+    let mut stats = vec![
+        ("Score", game.score.to_string()),
+        (
+            "Highest tile",
+            game.grid.max_tile().unwrap_or(0).to_string(),
+        ),
+        ("Moves", game.moves.to_string()),
+        ("Time", format_play_time(recorder.play_time())),
+    ];
+    if game.hints_used > 0 {
+        stats.push(("Hints", game.hints_used.to_string()));
+    }
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: styles::overlay_style(),
+                background_color: BackgroundColor(colours::GAME_OVER_OVERLAY),
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+            GameOverOverlay,
+            focus::Modal,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Game over!",
+                TextStyle {
+                    font: font_spec.family.clone(),
+                    font_size: 40.0,
+                    color: Color::WHITE,
+                },
+            ));
+            if game.new_best {
+                parent.spawn(TextBundle::from_section(
+                    "New best score!",
+                    TextStyle {
+                        font: font_spec.family.clone(),
+                        font_size: 25.0,
+                        color: colours::NEW_BEST,
+                    },
+                ));
+            }
+            for (label, value) in stats {
+                parent.spawn(TextBundle::from_section(
+                    format!("{label}: {value}"),
+                    TextStyle {
+                        font: font_spec.family.clone(),
+                        font_size: 20.0,
+                        color: Color::WHITE,
+                    },
+                ));
+            }
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        column_gap: Val::Px(20.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    spawn_button(parent, &font_spec, "New Game", ButtonAction::TryAgain);
+                    if history.can_undo() {
+                        spawn_styled_button(
+                            parent,
+                            &font_spec,
+                            "Undo last move",
+                            ButtonAction::Undo,
+                            styles::wide_button_style(),
+                        );
+                    }
+                });
+        });
+}
+
+/// Minutes and seconds, with hours in front once there are any.
+fn format_play_time(time: Duration) -> String {
+    let seconds = time.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+fn show_pause_overlay(mut commands: Commands, font_spec: Res<FontSpec>, keys: Res<KeyBindings>) {
+    let resume = match keys.bindings(Action::Pause).first() {
+        Some(binding) => format!("Press {binding} to carry on"),
+        None => "Pausing has no key bound".to_string(),
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: styles::overlay_style(),
+                background_color: BackgroundColor(colours::OVERLAY),
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+            PauseOverlay,
+            focus::Modal,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Paused",
+                TextStyle {
+                    font: font_spec.family.clone(),
+                    font_size: 40.0,
+                    color: Color::WHITE,
+                },
+            ));
+            parent.spawn(TextBundle::from_section(
+                resume,
+                TextStyle {
+                    font: font_spec.family.clone(),
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                },
+            ));
+            for (label, action) in [
+                ("Resume", ButtonAction::Resume),
+                ("Restart", ButtonAction::TryAgain),
+                ("Settings", ButtonAction::Settings),
+                ("Quit", ButtonAction::Quit),
+            ] {
+                spawn_button(parent, &font_spec, label, action);
+            }
+        });
+}
+
+/// The settings screen belongs to the pause menu, so it goes with it.
+fn close_settings(mut commands: Commands) {
+    commands.remove_resource::<SettingsOpen>();
+}
+
+fn show_settings_screen(mut commands: Commands, font_spec: Res<FontSpec>) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: styles::overlay_style(),
+                background_color: BackgroundColor(colours::OVERLAY),
+                z_index: ZIndex::Global(15),
+                ..default()
+            },
+            SettingsScreen,
+            focus::Modal,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Settings",
+                TextStyle {
+                    font: font_spec.family.clone(),
+                    font_size: 40.0,
+                    color: Color::WHITE,
+                },
+            ));
+            for toggle in SettingToggle::ALL {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            align_items: AlignItems::Center,
+                            column_gap: Val::Px(20.0),
+                            ..default()
+                        },
                         ..default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn(
+                            TextBundle::from_section(
+                                toggle.label(),
+                                TextStyle {
+                                    font: font_spec.family.clone(),
+                                    font_size: 20.0,
+                                    color: Color::WHITE,
+                                },
+                            )
+                            .with_style(Style {
+                                width: Val::Px(340.0),
+                                ..default()
+                            }),
+                        );
+                        spawn_button(parent, &font_spec, "", ButtonAction::Setting(toggle));
                     });
+            }
+            spawn_button(parent, &font_spec, "Keys", ButtonAction::Controls);
+            spawn_button(parent, &font_spec, "Done", ButtonAction::CloseSettings);
+        });
+}
+
+fn show_controls_screen(mut commands: Commands, font_spec: Res<FontSpec>) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: styles::overlay_style(),
+                background_color: BackgroundColor(colours::OVERLAY),
+                z_index: ZIndex::Global(20),
+                ..default()
+            },
+            ControlsScreen,
+            focus::Modal,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Controls",
+                TextStyle {
+                    font: font_spec.family.clone(),
+                    font_size: 40.0,
+                    color: Color::WHITE,
+                },
+            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        row_gap: Val::Px(5.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for action in Action::ALL {
+                        parent
+                            .spawn(NodeBundle {
+                                style: Style {
+                                    align_items: AlignItems::Center,
+                                    column_gap: Val::Px(20.0),
+                                    ..default()
+                                },
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                parent.spawn(
+                                    TextBundle::from_section(
+                                        action.label(),
+                                        TextStyle {
+                                            font: font_spec.family.clone(),
+                                            font_size: 20.0,
+                                            color: Color::WHITE,
+                                        },
+                                    )
+                                    .with_style(Style {
+                                        width: Val::Px(100.0),
+                                        ..default()
+                                    }),
+                                );
+                                spawn_styled_button(
+                                    parent,
+                                    &font_spec,
+                                    "",
+                                    ButtonAction::Rebind(action),
+                                    styles::binding_button_style(),
+                                );
+                            });
+                    }
+                });
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: font_spec.family.clone(),
+                        font_size: 15.0,
+                        color: Color::WHITE,
+                    },
+                ),
+                ControlsMessage,
+            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        column_gap: Val::Px(20.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for preset in Preset::ALL {
+                        spawn_button(
+                            parent,
+                            &font_spec,
+                            preset.label(),
+                            ButtonAction::Preset(preset),
+                        );
+                    }
                 });
+            spawn_button(parent, &font_spec, "Done", ButtonAction::CloseControls);
         });
 }
 
+fn controls_message(
+    rebinding: Res<Rebinding>,
+    mut query_message: Query<&mut Text, With<ControlsMessage>>,
+) {
+    for mut text in query_message.iter_mut() {
+        text.sections[0].value = match rebinding.capturing {
+            Some(action) if rebinding.message.is_empty() => {
+                format!("Press a key for {}, or Escape to cancel", action.label())
+            }
+            _ => rebinding.message.clone(),
+        };
+    }
+}
+
+pub fn despawn_with<T: Component>(mut commands: Commands, query: Query<Entity, With<T>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
 fn scoreboard(
     game: Res<Game>,
     mut query_scores: Query<&mut Text, (With<ScoreDisplay>, Without<BestScoreDisplay>)>,
@@ -161,27 +731,159 @@ fn scoreboard(
     text.sections[0].value = game.score_best.to_string();
 }
 
+/// Flashes the best score's box and relabels it the first time the best
+/// is beaten in a game, and puts it back for the next game.
+fn celebrate_best(
+    mut commands: Commands,
+    game: Res<Game>,
+    mut celebrating: Local<bool>,
+    query_box: Query<Entity, With<BestScoreBox>>,
+    mut query_label: Query<&mut Text, With<BestScoreLabel>>,
+) {
+    if game.new_best == *celebrating {
+        return;
+    }
+    *celebrating = game.new_best;
+
+    let mut label = query_label.single_mut();
+    if !game.new_best {
+        label.sections[0].value = "Best".to_string();
+        return;
+    }
+    label.sections[0].value = "New best!".to_string();
+
+    let flash = Duration::from_millis(250);
+    commands.entity(query_box.single()).insert(
+        BackgroundColor(colours::SCORE_BOX)
+            .ease_to(
+                BackgroundColor(colours::NEW_BEST),
+                EaseFunction::QuadraticOut,
+                EasingType::Once { duration: flash },
+            )
+            .ease_to(
+                BackgroundColor(colours::SCORE_BOX),
+                EaseFunction::QuadraticIn,
+                EasingType::Once {
+                    duration: flash * 3,
+                },
+            ),
+    );
+}
+
+fn seed_display(game: Res<Game>, mut query_seed: Query<&mut Text, With<SeedDisplay>>) {
+    if game.is_changed() {
+        let mut text = query_seed.single_mut();
+        text.sections[0].value = match game.hints_used {
+            0 => format!("Seed: {}", game.seed),
+            hints => format!("Seed: {} · Hints: {hints}", game.seed),
+        };
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn button_interaction(
     mut interaction_query: Query<
-        (&Interaction, &mut BackgroundColor),
-        (Changed<Interaction>, With<Button>),
+        (&Interaction, &mut BackgroundColor, &ButtonAction),
+        Changed<Interaction>,
     >,
     run_state: Res<State<RunState>>,
     mut next_state: ResMut<NextState<RunState>>,
+    mut settings: ResMut<Settings>,
+    mut commands: Commands,
+    mut keys: ResMut<KeyBindings>,
+    mut rebinding: Option<ResMut<Rebinding>>,
+    mut history_writer: EventWriter<HistoryEvent>,
+    mut exit: EventWriter<AppExit>,
+    mut confirm_writer: EventWriter<ConfirmRequest>,
+    mut confirmed_writer: EventWriter<confirm::Confirmed>,
+    confirming: Option<Res<Confirming>>,
 ) {
-    for (interaction, mut color) in interaction_query.iter_mut() {
+    for (interaction, mut color, action) in interaction_query.iter_mut() {
         match interaction {
             Interaction::Pressed => {
                 *color = colours::button::PRESSED.into();
 
-                match run_state.get() {
-                    RunState::Playing => {
-                        next_state.set(RunState::GameOver);
+                match (action, run_state.get()) {
+                    // A replay plays out on its own board, and starting a
+                    // game from it would carry that board over
+                    (
+                        ButtonAction::Toggle | ButtonAction::TryAgain | ButtonAction::BoardSize,
+                        RunState::Replay,
+                    ) => {}
+
+                    (ButtonAction::Toggle, RunState::Playing) => {
+                        confirm_writer.send(ConfirmRequest(Confirmable::EndGame));
+                    }
+
+                    (ButtonAction::Toggle, _) | (ButtonAction::TryAgain, _) => {
+                        confirm_writer.send(ConfirmRequest(Confirmable::Restart));
+                    }
+
+                    (ButtonAction::Confirm, _) => {
+                        if let Some(Confirming(confirmable)) = confirming.as_deref() {
+                            confirmed_writer.send(confirm::Confirmed(*confirmable));
+                            commands.remove_resource::<Confirming>();
+                        }
+                    }
+
+                    (ButtonAction::Cancel, _) => {
+                        commands.remove_resource::<Confirming>();
                     }
 
-                    RunState::GameOver => {
+                    (ButtonAction::Undo, _) => {
+                        history_writer.send(HistoryEvent::Undo);
+                    }
+
+                    (ButtonAction::Resume, _) => {
                         next_state.set(RunState::Playing);
                     }
+
+                    (ButtonAction::Settings, _) => {
+                        commands.init_resource::<SettingsOpen>();
+                    }
+
+                    (ButtonAction::Setting(toggle), _) => {
+                        toggle.flip(&mut settings);
+                    }
+
+                    (ButtonAction::CloseSettings, _) => {
+                        commands.remove_resource::<SettingsOpen>();
+                    }
+
+                    (ButtonAction::Quit, _) => {
+                        exit.send(AppExit);
+                    }
+
+                    (ButtonAction::KeepGoing, _) => {
+                        next_state.set(RunState::Playing);
+                    }
+
+                    (ButtonAction::BoardSize, _) => {
+                        confirm_writer.send(ConfirmRequest(Confirmable::ChangeBoardSize));
+                    }
+
+                    (ButtonAction::Controls, _) => {
+                        commands.init_resource::<Rebinding>();
+                    }
+
+                    (ButtonAction::Rebind(action), _) => {
+                        if let Some(rebinding) = rebinding.as_mut() {
+                            rebinding.capturing = Some(*action);
+                            rebinding.message.clear();
+                        }
+                    }
+
+                    (ButtonAction::Preset(preset), _) => {
+                        *keys = KeyBindings::preset(*preset);
+                        if let Some(rebinding) = rebinding.as_mut() {
+                            rebinding.capturing = None;
+                            rebinding.message.clear();
+                        }
+                    }
+
+                    (ButtonAction::CloseControls, _) => {
+                        commands.remove_resource::<Rebinding>();
+                    }
                 }
             }
             Interaction::Hovered => {
@@ -195,23 +897,39 @@ fn button_interaction(
 }
 
 fn button_text(
-    button_query: Query<&Children, With<Button>>,
+    button_query: Query<(&ButtonAction, &Children)>,
     mut text_query: Query<&mut Text>,
     run_state: Res<State<RunState>>,
+    settings: Res<Settings>,
+    keys: Res<KeyBindings>,
+    rebinding: Option<Res<Rebinding>>,
+    player: Option<Res<ReplayPlayer>>,
 ) {
-    let children = button_query.single();
-    let first_child_entity = children
-        .first()
-        .expect("expect button to have a first child");
+    let capturing = rebinding.and_then(|rebinding| rebinding.capturing);
+
+    for (action, children) in button_query.iter() {
+        let label = match (action, run_state.get()) {
+            (ButtonAction::Toggle, RunState::Playing) => "End Game".to_string(),
+            (ButtonAction::Toggle, RunState::Replay) => "Replay".to_string(),
+            (ButtonAction::Toggle, _) => "New Game".to_string(),
+            (ButtonAction::BoardSize, _) => {
+                let (width, height) = board_size(&settings, player.as_deref());
+                format!("{width}x{height}")
+            }
+            (ButtonAction::Rebind(action), _) if capturing == Some(*action) => "...".to_string(),
+            (ButtonAction::Rebind(action), _) => keys.bindings(*action).iter().join(", "),
+            (ButtonAction::Setting(toggle), _) if toggle.is_on(&settings) => "On".to_string(),
+            (ButtonAction::Setting(_), _) => "Off".to_string(),
+            _ => continue,
+        };
 
-    let mut text = text_query.get_mut(*first_child_entity).unwrap();
+        let first_child_entity = children
+            .first()
+            .expect("expect button to have a first child");
+        let mut text = text_query.get_mut(*first_child_entity).unwrap();
 
-    match run_state.get() {
-        RunState::Playing => {
-            text.sections[0].value = "End Game".to_string();
-        }
-        RunState::GameOver => {
-            text.sections[0].value = "New Game".to_string();
+        if text.sections[0].value != label {
+            text.sections[0].value = label;
         }
     }
 }
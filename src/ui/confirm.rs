@@ -0,0 +1,184 @@
+//! Asks before throwing away a game in progress.
+//!
+//! Anything that would lose the current game sends a [`ConfirmRequest`]
+//! instead of acting straight away. Unless there's nothing to lose, or
+//! `confirm_destructive` is off, a dialog asks first; Enter confirms and
+//! Escape cancels.
+
+use super::{despawn_with, focus, spawn_button, styles, ButtonAction};
+use crate::{colours, FontSpec, Game, NewGameEvent, RunState, Settings};
+use bevy::prelude::*;
+
+/// Something that ends the game being played.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Confirmable {
+    EndGame,
+    Restart,
+    /// Switches to the next board size, which starts a new game.
+    ChangeBoardSize,
+}
+
+impl Confirmable {
+    fn question(&self) -> &'static str {
+        match self {
+            Confirmable::EndGame => "End this game?",
+            Confirmable::Restart => "Start a new game?",
+            Confirmable::ChangeBoardSize => "Change the board size?",
+        }
+    }
+
+    fn verb(&self) -> &'static str {
+        match self {
+            Confirmable::EndGame => "End game",
+            Confirmable::Restart => "Restart",
+            Confirmable::ChangeBoardSize => "Change",
+        }
+    }
+}
+
+/// Asks for `Confirmable` to be done, once confirmed if need be.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ConfirmRequest(pub Confirmable);
+
+/// Goes ahead with `Confirmable`.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct Confirmed(pub Confirmable);
+
+/// The confirmation dialog is up, asking about this, while the resource
+/// exists.
+#[derive(Resource, Debug)]
+pub struct Confirming(pub Confirmable);
+
+#[derive(Component)]
+pub struct ConfirmDialog;
+
+pub struct ConfirmPlugin;
+
+impl Plugin for ConfirmPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ConfirmRequest>()
+            .add_event::<Confirmed>()
+            .add_systems(
+                Update,
+                (
+                    (
+                        request_confirmation,
+                        confirm_keys.run_if(resource_exists::<Confirming>),
+                        apply_confirmed,
+                    )
+                        .chain(),
+                    show_confirm_dialog.run_if(resource_added::<Confirming>),
+                    despawn_with::<ConfirmDialog>.run_if(resource_removed::<Confirming>()),
+                ),
+            );
+    }
+}
+
+fn request_confirmation(
+    mut commands: Commands,
+    mut request_reader: EventReader<ConfirmRequest>,
+    mut confirmed_writer: EventWriter<Confirmed>,
+    settings: Res<Settings>,
+    game: Res<Game>,
+    run_state: Res<State<RunState>>,
+    confirming: Option<Res<Confirming>>,
+) {
+    // Nothing is lost by ending a game that's over, a replay or a game
+    // that hasn't started
+    let nothing_to_lose =
+        game.moves == 0 || matches!(run_state.get(), RunState::GameOver | RunState::Replay);
+
+    for ConfirmRequest(confirmable) in request_reader.read() {
+        if !settings.confirm_destructive || nothing_to_lose {
+            confirmed_writer.send(Confirmed(*confirmable));
+        } else if confirming.is_none() {
+            commands.insert_resource(Confirming(*confirmable));
+        }
+    }
+}
+
+fn confirm_keys(
+    mut commands: Commands,
+    input: Res<ButtonInput<KeyCode>>,
+    confirming: Res<Confirming>,
+    mut confirmed_writer: EventWriter<Confirmed>,
+) {
+    if input.any_just_pressed([KeyCode::Enter, KeyCode::NumpadEnter]) {
+        confirmed_writer.send(Confirmed(confirming.0));
+        commands.remove_resource::<Confirming>();
+    } else if input.just_pressed(KeyCode::Escape) {
+        commands.remove_resource::<Confirming>();
+    }
+}
+
+fn apply_confirmed(
+    mut confirmed_reader: EventReader<Confirmed>,
+    mut settings: ResMut<Settings>,
+    mut new_game: EventWriter<NewGameEvent>,
+    mut next_state: ResMut<NextState<RunState>>,
+) {
+    for Confirmed(confirmable) in confirmed_reader.read() {
+        match confirmable {
+            Confirmable::EndGame => next_state.set(RunState::GameOver),
+            Confirmable::Restart => {
+                new_game.send(NewGameEvent);
+            }
+            Confirmable::ChangeBoardSize => settings.cycle_board_size(),
+        }
+    }
+}
+
+fn show_confirm_dialog(
+    mut commands: Commands,
+    font_spec: Res<FontSpec>,
+    confirming: Res<Confirming>,
+) {
+    let Confirming(confirmable) = *confirming;
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: styles::overlay_style(),
+                background_color: BackgroundColor(colours::OVERLAY),
+                z_index: ZIndex::Global(25),
+                ..default()
+            },
+            ConfirmDialog,
+            focus::Modal,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                confirmable.question(),
+                TextStyle {
+                    font: font_spec.family.clone(),
+                    font_size: 40.0,
+                    color: Color::WHITE,
+                },
+            ));
+            parent.spawn(TextBundle::from_section(
+                "The game in progress will be lost. Enter to confirm, Escape to cancel.",
+                TextStyle {
+                    font: font_spec.family.clone(),
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                },
+            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        column_gap: Val::Px(20.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    spawn_button(
+                        parent,
+                        &font_spec,
+                        confirmable.verb(),
+                        ButtonAction::Confirm,
+                    );
+                    spawn_button(parent, &font_spec, "Cancel", ButtonAction::Cancel);
+                });
+        });
+}
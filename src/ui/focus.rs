@@ -0,0 +1,155 @@
+//! Lets a gamepad move between buttons and press them.
+//!
+//! While an overlay is up its buttons have focus. Otherwise the board does,
+//! until Select hands focus to the header buttons.
+
+use super::ButtonAction;
+use crate::colours;
+use bevy::prelude::*;
+use two_oh_four_eight::engine::BoardShift;
+
+/// An overlay that covers the rest of the UI. Only the buttons on the
+/// topmost one can be focused.
+#[derive(Component)]
+pub struct Modal;
+
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuEvent {
+    /// Focuses the next button in that direction.
+    Move(BoardShift),
+    /// Presses the focused button.
+    Press,
+    /// Switches between the header buttons and the board.
+    Toggle,
+    /// Gives focus back to the board.
+    Back,
+}
+
+#[derive(Resource, Default)]
+pub struct MenuFocus {
+    focused: Option<Entity>,
+    /// The header buttons have focus rather than the board.
+    header: bool,
+    /// An overlay with buttons is up.
+    modal: bool,
+    /// The button pressed last frame, to be released again.
+    pressed: Option<Entity>,
+}
+
+impl MenuFocus {
+    /// Whether gamepad input goes to the buttons rather than the board.
+    pub fn engaged(&self) -> bool {
+        self.header || self.modal
+    }
+
+    /// Keeps focus on one of `focusable`, or on nothing when the board has it.
+    fn refocus(&mut self, focusable: &[Entity]) {
+        if !self.engaged() {
+            self.focused = None;
+        } else if !self
+            .focused
+            .is_some_and(|entity| focusable.contains(&entity))
+        {
+            self.focused = focusable.first().copied();
+        }
+    }
+}
+
+pub fn menu_navigation(
+    mut menu_reader: EventReader<MenuEvent>,
+    mut focus: ResMut<MenuFocus>,
+    mut buttons: Query<(Entity, &GlobalTransform, &mut Interaction), With<ButtonAction>>,
+    modals: Query<(Entity, &ZIndex), With<Modal>>,
+    parents: Query<&Parent>,
+) {
+    if let Some(entity) = focus.pressed.take() {
+        if let Ok((_, _, mut interaction)) = buttons.get_mut(entity) {
+            if *interaction == Interaction::Pressed {
+                *interaction = Interaction::None;
+            }
+        }
+    }
+
+    let top = modals
+        .iter()
+        .max_by_key(|(_, z_index)| match z_index {
+            ZIndex::Local(z) | ZIndex::Global(z) => *z,
+        })
+        .map(|(entity, _)| entity);
+
+    let mut focusable: Vec<(Entity, Vec2)> = buttons
+        .iter()
+        .filter(|(entity, _, _)| {
+            parents
+                .iter_ancestors(*entity)
+                .find(|ancestor| modals.contains(*ancestor))
+                == top
+        })
+        .map(|(entity, transform, _)| (entity, transform.translation().truncate()))
+        .collect();
+    // Reading order, UI coordinates grow downwards
+    focusable.sort_by(|(_, a), (_, b)| a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x)));
+    let focusable: Vec<Entity> = focusable.into_iter().map(|(entity, _)| entity).collect();
+
+    focus.modal = top.is_some() && !focusable.is_empty();
+    if focus.modal {
+        focus.header = false;
+    }
+    focus.refocus(&focusable);
+
+    for event in menu_reader.read() {
+        match event {
+            MenuEvent::Toggle if !focus.modal => {
+                focus.header = !focus.header;
+            }
+            MenuEvent::Back => {
+                focus.header = false;
+            }
+            MenuEvent::Move(shift) => {
+                let Some(idx) = focus
+                    .focused
+                    .and_then(|focused| focusable.iter().position(|entity| *entity == focused))
+                else {
+                    continue;
+                };
+                let next = match shift {
+                    BoardShift::Left | BoardShift::Up => idx + focusable.len() - 1,
+                    BoardShift::Right | BoardShift::Down => idx + 1,
+                };
+                focus.focused = Some(focusable[next % focusable.len()]);
+            }
+            MenuEvent::Press => {
+                let Some(entity) = focus.focused else {
+                    continue;
+                };
+                if let Ok((_, _, mut interaction)) = buttons.get_mut(entity) {
+                    *interaction = Interaction::Pressed;
+                    focus.pressed = Some(entity);
+                }
+            }
+            MenuEvent::Toggle => {}
+        }
+        focus.refocus(&focusable);
+    }
+}
+
+/// Shows the focused button as if the mouse were over it.
+pub fn focus_highlight(
+    focus: Res<MenuFocus>,
+    mut buttons: Query<(Entity, &Interaction, &mut BackgroundColor), With<ButtonAction>>,
+) {
+    for (entity, interaction, mut color) in buttons.iter_mut() {
+        if *interaction != Interaction::None {
+            continue;
+        }
+
+        let wanted = if focus.focused == Some(entity) {
+            colours::button::HOVERED
+        } else {
+            colours::button::NORMAL
+        };
+        if color.0 != wanted {
+            color.0 = wanted;
+        }
+    }
+}
@@ -13,3 +13,45 @@ pub fn score_container_style() -> Style {
         ..Default::default()
     }
 }
+
+pub fn button_style() -> Style {
+    Style {
+        width: Val::Px(130.0),
+        height: Val::Px(50.0),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..Default::default()
+    }
+}
+
+/// For labels too long for [`button_style`].
+pub fn wide_button_style() -> Style {
+    Style {
+        width: Val::Px(200.0),
+        ..button_style()
+    }
+}
+
+/// The buttons on the controls screen, wide enough for a few key names.
+pub fn binding_button_style() -> Style {
+    Style {
+        width: Val::Px(260.0),
+        height: Val::Px(36.0),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..Default::default()
+    }
+}
+
+pub fn overlay_style() -> Style {
+    Style {
+        position_type: PositionType::Absolute,
+        height: Val::Percent(100.0),
+        width: Val::Percent(100.0),
+        flex_direction: FlexDirection::Column,
+        align_items: AlignItems::Center,
+        justify_content: JustifyContent::Center,
+        row_gap: Val::Px(20.0),
+        ..Default::default()
+    }
+}